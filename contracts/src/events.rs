@@ -0,0 +1,296 @@
+use soroban_sdk::{contractevent, Address, Symbol, Vec};
+
+use crate::{MarketMetadata, PricingModel, Role, SettlementMode};
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Initialized {
+    #[topic]
+    pub admin: Address,
+    pub token: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdminTransferProposed {
+    #[topic]
+    pub admin: Address,
+    #[topic]
+    pub new_admin: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdminTransferred {
+    #[topic]
+    pub previous_admin: Address,
+    #[topic]
+    pub new_admin: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdminTransferCancelled {
+    #[topic]
+    pub admin: Address,
+    #[topic]
+    pub new_admin: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarketCreated {
+    #[topic]
+    pub market_id: u64,
+    pub metadata: MarketMetadata,
+    pub start_time: i64,
+    pub odds: Vec<i128>,
+    pub depth: i128,
+    pub pricing: PricingModel,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarketMetadataUpdated {
+    #[topic]
+    pub market_id: u64,
+    pub metadata: MarketMetadata,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BettingWindowUpdated {
+    #[topic]
+    pub market_id: u64,
+    pub close_time: i64,
+    pub in_play_close: Option<i64>,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarketDepthUpdated {
+    #[topic]
+    pub market_id: u64,
+    pub depth: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettlementModeUpdated {
+    #[topic]
+    pub market_id: u64,
+    pub mode: SettlementMode,
+}
+
+// Prices derived from the reserves before and after they were re-seeded
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OddsUpdated {
+    #[topic]
+    pub market_id: u64,
+    pub old_odds: Vec<i128>,
+    pub new_odds: Vec<i128>,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarketArchived {
+    #[topic]
+    pub market_id: u64,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarketVoided {
+    #[topic]
+    pub market_id: u64,
+    pub reason: Symbol,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettlementStarted {
+    #[topic]
+    pub market_id: u64,
+    pub outcome: u32,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarketSettled {
+    #[topic]
+    pub market_id: u64,
+    pub outcome: u32,
+    pub total_payout: i128,
+}
+
+// One per stake removed by settlement, so indexers can close out every stake
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StakeSettled {
+    #[topic]
+    pub market_id: u64,
+    #[topic]
+    pub staker: Address,
+    pub stake_id: u64,
+    pub payout: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StakeRefunded {
+    #[topic]
+    pub market_id: u64,
+    #[topic]
+    pub staker: Address,
+    pub stake_id: u64,
+    pub refund: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Deposited {
+    #[topic]
+    pub user: Address,
+    pub amount: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Withdrawn {
+    #[topic]
+    pub user: Address,
+    pub amount: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LiquidityProvided {
+    #[topic]
+    pub provider: Address,
+    pub amount: i128,
+    pub shares: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LiquidityPoolReset {
+    #[topic]
+    pub epoch: u32,
+    pub written_off_shares: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LiquidityWithdrawn {
+    #[topic]
+    pub provider: Address,
+    pub shares: i128,
+    pub amount: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StakePlaced {
+    #[topic]
+    pub market_id: u64,
+    #[topic]
+    pub staker: Address,
+    pub stake_id: u64,
+    pub outcome: u32,
+    pub amount: i128,
+    pub shares: i128,
+    pub avg_price: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CashedOut {
+    #[topic]
+    pub market_id: u64,
+    #[topic]
+    pub staker: Address,
+    pub stake_id: u64,
+    pub gross_payout: i128,
+    pub fee: i128,
+    pub net_payout: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompleteSetMinted {
+    #[topic]
+    pub market_id: u64,
+    #[topic]
+    pub user: Address,
+    pub amount: i128,
+    pub stake_ids: Vec<u64>,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompleteSetRedeemed {
+    #[topic]
+    pub market_id: u64,
+    #[topic]
+    pub user: Address,
+    pub amount: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoleGranted {
+    #[topic]
+    pub role: Role,
+    #[topic]
+    pub account: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoleRevoked {
+    #[topic]
+    pub role: Role,
+    #[topic]
+    pub account: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContractPaused {
+    #[topic]
+    pub by: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContractUnpaused {
+    #[topic]
+    pub by: Address,
+}
+
+// Fee percentages charged on cash-outs before and after the change
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CashoutFeeUpdated {
+    #[topic]
+    pub by: Address,
+    pub old: i128,
+    pub new: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarketSuspended {
+    #[topic]
+    pub market_id: u64,
+    pub by: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarketResumed {
+    #[topic]
+    pub market_id: u64,
+    pub by: Address,
+}
@@ -1,40 +1,190 @@
 #![no_std]
+// Contract entry points take their arguments flat, and the generated client mirrors them
+#![allow(clippy::too_many_arguments)]
 
-use soroban_sdk::{contract, contractimpl, contracttype, symbol_short, Env, Address, Vec, Symbol};
+use soroban_sdk::{contract, contracterror, contractimpl, contracttype, symbol_short, token, Env, Address, IntoVal, Map, String, Val, Vec, Symbol};
 
-const DECIMALS: i128 = 1_000_000; // USDC-like 6 decimals
+mod events;
+mod lmsr;
+pub use events::*;
+
+pub(crate) const DECIMALS: i128 = 1_000_000; // USDC-like 6 decimals
 const MIN_PRICE: i128 = 10_000; // $0.01
-const TOTAL_PRICE_SUM: i128 = 990_000; // $0.99 (includes spread @ 1%)
+pub(crate) const TOTAL_PRICE_SUM: i128 = 990_000; // $0.99 (includes spread @ 1%)
 const MAX_STAKERS_PER_MARKET: u32 = 1000;
-const CASHOUT_FEE_PERCENT: i128 = 5; // 5% fee on early cashout
+const MIN_OUTCOMES: u32 = 2;
+const MAX_OUTCOMES: u32 = 32;
+const MAX_METADATA_LEN: u32 = 256; // bytes per metadata string
+const MIN_MARKET_DEPTH: i128 = DECIMALS; // $1 of virtual liquidity
+const MIN_LOCKED_LP_SHARES: i128 = 1_000; // minted to nobody by the first deposit into an empty pool
+// Stakes per settlement call. Each stake writes at most two entries (its removal and the owner's
+// balance), and the batch adds the market, the instance, CLOSING and up to two index pages:
+// 2 * 20 + 5 = 45, under mainnet's 50 write entries per transaction
+const MAX_SETTLE_BATCH: u32 = 20;
+const STAKE_PAGE_SIZE: u32 = 100; // stake ids per MKTSTKS page, so no index entry outgrows the ledger's entry size limit
+const MAX_BUMP_BATCH: u32 = 50; // stakes per bump_market call, keeps the footprint under network limits
+const DEFAULT_CASHOUT_FEE_PERCENT: i128 = 5; // 5% fee on early cashout
+const MAX_CASHOUT_FEE_PERCENT: i128 = 20;
+
+// Storage TTLs, in ledgers (~5s each)
+const DAY_IN_LEDGERS: u32 = 17280;
+const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS;
+const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+const BALANCE_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS; // user balances and LP positions
+const BALANCE_LIFETIME_THRESHOLD: u32 = BALANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+const MARKET_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS; // markets and their stakes
+const MARKET_LIFETIME_THRESHOLD: u32 = MARKET_BUMP_AMOUNT - DAY_IN_LEDGERS;
+const ROLE_BUMP_AMOUNT: u32 = 120 * DAY_IN_LEDGERS; // role grants, long enough for a rarely used guardian
+const ROLE_LIFETIME_THRESHOLD: u32 = ROLE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+fn key_config() -> Symbol { symbol_short!("CONFIG") }
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum Error {
+    NotInitialized = 2,
+    Unauthorized = 3,
+    NotStakeOwner = 4,
+    InvalidAmount = 5,
+    InvalidOutcome = 6,
+    OddsBelowMinimum = 7,
+    InvalidOddsSum = 8,
+    MarketNotFound = 9,
+    MarketNotActive = 10,
+    StakeNotFound = 11,
+    StakerCapReached = 12,
+    InsufficientBalance = 13,
+    InsufficientLpBalance = 14,
+    InsufficientLiquidity = 15,
+    AmountTooSmall = 17,
+    Overflow = 18,
+    InvalidFee = 19,
+    MarketNotSettling = 20,
+    MarketNotSettled = 21,
+    NotWinningStake = 22,
+    ExposureLimitExceeded = 23,
+    SlippageExceeded = 24,
+    DeadlinePassed = 25,
+    InvalidDepth = 26,
+    IncompleteSet = 27,
+    InsufficientShares = 28,
+    InvalidOutcomeCount = 29,
+    InvalidMetadata = 30,
+    BettingClosed = 31,
+    InvalidBettingWindow = 32,
+    ContractPaused = 33,
+    NoPendingAdmin = 34,
+    ReserveDepleted = 35,
+    LiquidityLocked = 36,
+    SettlementModeMismatch = 37,
+    UnmintedSet = 38,
+}
 
-fn key_admin() -> Symbol { symbol_short!("ADMIN") }
-fn key_market_counter() -> Symbol { symbol_short!("MKT_CNT") }
-fn key_stake_counter() -> Symbol { symbol_short!("STK_CNT") }
-fn key_total_liquidity() -> Symbol { symbol_short!("TOT_LIQ") }
+/// Global contract configuration and counters, kept in instance storage and loaded once per call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct Config {
+    pub admin: Address,
+    pub pending_admin: Option<Address>, // proposed by the admin, takes over once it accepts
+    pub token: Address,
+    pub market_counter: u64,
+    pub stake_counter: u64,
+    pub total_liquidity: i128,
+    pub total_lp_shares: i128,
+    pub lp_epoch: u32, // bumped when a drained pool writes off its shares; older LP accounts hold nothing
+    pub total_exposure: i128, // sum of every market's reserved exposure, never above total_liquidity
+    pub cashout_fee_percent: i128,
+    pub paused: bool, // blocks trading and liquidity changes; balances can still be withdrawn
+}
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[contracttype]
 pub struct Market {
     pub id: u64,
-    pub title: Symbol,
     pub start_time: i64,
-    pub odds_home: i128,
-    pub odds_draw: i128,
-    pub odds_away: i128,
+    pub close_time: i64, // pre-match betting closes here, defaults to start_time
+    pub in_play_close: Option<i64>, // if set, betting stays open in-play until this time
+    pub odds: Vec<i128>, // one price per outcome, summing to TOTAL_PRICE_SUM
+    pub depth: i128, // virtual liquidity the reserves were last seeded with (LMSR `b`); higher means less price impact
+    pub pricing: PricingModel,
+    pub settlement: SettlementMode,
     pub status: MarketStatus,
     pub staker_count: u32,
-    pub reserve_home: i128,
-    pub reserve_draw: i128,
-    pub reserve_away: i128,
+    pub reserves: Vec<i128>, // one per outcome; LMSR markets keep their outstanding-share state here
+    pub winning_outcome: Option<u32>,
+    pub resolved_by_claim: bool, // resolved with resolve_market, so winners are paid through claim
+    pub settle_cursor: u32, // index into MKTSTKS of the next stake to settle
+    pub listed_stakes: u32, // stake ids written to the MKTSTKS pages, closed stakes included
+    pub total_payout: i128, // winnings paid, or cost refunded if the market was voided
+    pub void_reason: Option<Symbol>,
+    pub shares: Vec<i128>, // outstanding shares per outcome, i.e. what the pool owes if it wins
+    pub exposure: i128, // pool liquidity reserved: worst-case loss while Active, unpaid winnings while Settling
+    pub escrow: i128, // cost of open stakes while Active or Voiding, unclaimed winnings once resolved
+    pub pending_close: Option<i64>, // close time the market is counted under in CLOSING, while it has exposure
+}
+
+/// Display details of a market, stored in their own entry next to the `Market`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct MarketMetadata {
+    pub title: String,
+    pub home_team: String, // empty for markets that aren't a single fixture, e.g. outrights
+    pub away_team: String,
+    pub competition: String,
+    pub venue: String,
+    pub fixture_id: String, // id of the fixture in the external data feed
+    pub outcome_labels: Vec<String>, // one per outcome, indexed by `Stake::outcome`
+    pub image_uri: String,
+}
+
+/// Duties the admin can delegate. The admin itself holds every role.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[contracttype]
+pub enum Role {
+    /// Creates markets and edits their metadata and betting window.
+    MarketCreator,
+    /// Moves odds and depth of open markets.
+    OddsManager,
+    /// Settles, resolves, voids and archives markets.
+    Settler,
+    /// Pauses the contract and suspends markets.
+    Guardian,
+    /// Sets the cash-out fee.
+    Treasurer,
+}
+
+/// How a market prices stakes and cash-outs from its reserves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[contracttype]
+pub enum PricingModel {
+    /// Price is the outcome's share of the total reserve; stakes add their cost to the reserve.
+    ReserveRatio,
+    /// Logarithmic Market Scoring Rule with `b = depth`; reserves hold the outstanding-share state.
+    Lmsr,
+}
+
+/// How the winners of a market get paid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[contracttype]
+pub enum SettlementMode {
+    /// `settle_market` pays every stake in batches, or `resolve_market` leaves them to `claim`.
+    /// Capped at MAX_STAKERS_PER_MARKET stakes so settlement finishes in a bounded number of calls.
+    Push,
+    /// Only `resolve_market` and `claim`, which cost the same however many stakes there are, so
+    /// the market has no staker cap.
+    Pull,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[contracttype]
 pub enum MarketStatus {
     Active,
+    Settling,
     Settled,
+    Voiding,
     Archived,
+    Suspended, // trading halted by a guardian; resumes as Active
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -43,9 +193,27 @@ pub struct Stake {
     pub id: u64,
     pub staker: Address,
     pub market_id: u64,
-    pub outcome: u32, // 0 = home, 1 = draw, 2 = away
+    pub outcome: u32, // index into the market's outcomes
     pub amount: i128, // locked USD amount (6 decimals)
     pub price: i128,  // odds at time of stake (6 decimals)
+    pub cost: i128,   // amount paid for the shares, refunded if the market is voided
+    pub minted: bool, // a leg of a complete set bought at $1 with mint_complete_set
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct LpAccount {
+    pub shares: i128,
+    pub deposited: i128, // cost basis of the shares still held (6 decimals)
+    pub epoch: u32, // Config::lp_epoch the shares were minted in
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct LpPosition {
+    pub shares: i128,
+    pub value: i128, // redeemable now: pro-rata share of the liquidity not reserved for open markets
+    pub pnl: i128,   // value minus cost basis
 }
 
 #[contract]
@@ -54,180 +222,475 @@ pub struct PredictionMarketContract;
 #[contractimpl]
 impl PredictionMarketContract {
 
-    pub fn initialize(env: Env, admin: Address) {
-        if env.storage().persistent().has(&key_admin()) {
-            panic!("already initialized");
-        }
-        env.storage().persistent().set(&key_admin(), &admin);
-        env.storage().persistent().set(&key_market_counter(), &0u64);
-        env.storage().persistent().set(&key_stake_counter(), &0u64);
-        env.storage().persistent().set(&key_total_liquidity(), &0i128);
+    /// Sets the admin and collateral token as part of deployment, so no one can initialize the
+    /// contract ahead of the deployer.
+    pub fn __constructor(env: Env, admin: Address, token: Address) {
+        let config = Config {
+            admin: admin.clone(),
+            pending_admin: None,
+            token: token.clone(),
+            market_counter: 0,
+            stake_counter: 0,
+            total_liquidity: 0,
+            total_lp_shares: 0,
+            lp_epoch: 0,
+            total_exposure: 0,
+            cashout_fee_percent: DEFAULT_CASHOUT_FEE_PERCENT,
+            paused: false,
+        };
+        Self::save_config(&env, &config);
+        Self::extend_instance(&env);
+        Initialized { admin, token }.publish(&env);
     }
 
     pub fn create_market(
         env: Env,
-        admin: Address,
-        title: Symbol,
+        caller: Address,
+        metadata: MarketMetadata,
         start_time: i64,
-        odds_home: i128,
-        odds_draw: i128,
-        odds_away: i128,
-    ) -> u64 {
-        Self::require_admin(&env, &admin);
-        Self::validate_odds(odds_home, odds_draw, odds_away);
-        let mut counter: u64 = env.storage().persistent().get(&key_market_counter()).unwrap_or(0u64);
-        counter += 1;
-        env.storage().persistent().set(&key_market_counter(), &counter);
-        const BASE_RESERVE_MULTIPLIER: i128 = 1000; // $1000 initial reserve
-        let reserve_home = odds_home.checked_mul(BASE_RESERVE_MULTIPLIER).expect("mul overflow");
-        let reserve_draw = odds_draw.checked_mul(BASE_RESERVE_MULTIPLIER).expect("mul overflow");
-        let reserve_away = odds_away.checked_mul(BASE_RESERVE_MULTIPLIER).expect("mul overflow");
-        let market = Market {
+        odds: Vec<i128>,
+        depth: i128,
+        pricing: PricingModel,
+    ) -> Result<u64, Error> {
+        let mut config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::MarketCreator)?;
+        Self::extend_instance(&env);
+        if odds.len() < MIN_OUTCOMES || odds.len() > MAX_OUTCOMES { return Err(Error::InvalidOutcomeCount); }
+        Self::validate_odds(&odds)?;
+        Self::validate_metadata(&metadata, odds.len())?;
+        if depth < MIN_MARKET_DEPTH { return Err(Error::InvalidDepth); }
+        config.market_counter += 1;
+        let counter = config.market_counter;
+        Self::save_config(&env, &config);
+        let mut market = Market {
             id: counter,
-            title: title.clone(),
             start_time,
-            odds_home,
-            odds_draw,
-            odds_away,
+            close_time: start_time,
+            in_play_close: None,
+            odds: odds.clone(),
+            depth,
+            pricing,
+            settlement: SettlementMode::Push,
             status: MarketStatus::Active,
             staker_count: 0u32,
-            reserve_home,
-            reserve_draw,
-            reserve_away,
+            reserves: Self::zeroes(&env, odds.len()),
+            winning_outcome: None,
+            resolved_by_claim: false,
+            settle_cursor: 0,
+            listed_stakes: 0,
+            total_payout: 0,
+            void_reason: None,
+            shares: Self::zeroes(&env, odds.len()),
+            exposure: 0,
+            escrow: 0,
+            pending_close: None,
         };
+        Self::seed_reserves(&mut market, depth)?;
         let market_key = Self::market_key(counter);
         env.storage().persistent().set(&market_key, &market);
-        let mkstakes_key = Self::market_stakes_key(counter);
-        let empty_vec: Vec<u64> = Vec::new(&env);
-        env.storage().persistent().set(&mkstakes_key, &empty_vec);
-        counter
+        let metadata_key = Self::market_metadata_key(counter);
+        env.storage().persistent().set(&metadata_key, &metadata);
+        Self::extend_market(&env, &market_key);
+        Self::extend_market(&env, &metadata_key);
+        MarketCreated { market_id: counter, metadata, start_time, odds, depth, pricing }.publish(&env);
+        Ok(counter)
     }
 
     pub fn update_odds(
         env: Env,
-        admin: Address,
+        caller: Address,
         market_id: u64,
-        odds_home: i128,
-        odds_draw: i128,
-        odds_away: i128,
-    ) {
-        Self::require_admin(&env, &admin);
-        Self::validate_odds(odds_home, odds_draw, odds_away);
+        odds: Vec<i128>,
+    ) -> Result<(), Error> {
+        let config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::OddsManager)?;
+        Self::extend_instance(&env);
         let market_key = Self::market_key(market_id);
-        let mut market: Market = env.storage().persistent().get(&market_key).expect("market not found");
-        market.odds_home = odds_home;
-        market.odds_draw = odds_draw;
-        market.odds_away = odds_away;
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if !Self::is_open(&market) { return Err(Error::MarketNotActive); }
+        if odds.len() != market.odds.len() { return Err(Error::InvalidOutcomeCount); }
+        Self::validate_odds(&odds)?;
+        let old_odds = Self::current_prices(&market)?;
+        market.odds = odds;
+        // Pricing comes from the reserves, so re-seed them to the new odds at the same depth.
+        // LMSR markets are re-seeded from `depth`, which is their `b`.
+        let total_reserve = Self::sum(&market.reserves)?;
+        Self::seed_reserves(&mut market, total_reserve)?;
+        let new_odds = Self::current_prices(&market)?;
         env.storage().persistent().set(&market_key, &market);
+        Self::extend_market(&env, &market_key);
+        OddsUpdated { market_id, old_odds, new_odds }.publish(&env);
+        Ok(())
+    }
+
+    /// Replaces the display details of an Active market. Outcome labels must still match the
+    /// market's outcomes.
+    pub fn update_market_metadata(env: Env, caller: Address, market_id: u64, metadata: MarketMetadata) -> Result<(), Error> {
+        let config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::MarketCreator)?;
+        Self::extend_instance(&env);
+        let market: Market = env.storage().persistent().get(&Self::market_key(market_id)).ok_or(Error::MarketNotFound)?;
+        if !Self::is_open(&market) { return Err(Error::MarketNotActive); }
+        Self::validate_metadata(&metadata, market.odds.len())?;
+        let metadata_key = Self::market_metadata_key(market_id);
+        env.storage().persistent().set(&metadata_key, &metadata);
+        Self::extend_market(&env, &metadata_key);
+        MarketMetadataUpdated { market_id, metadata }.publish(&env);
+        Ok(())
     }
 
-    pub fn archive_market(env: Env, admin: Address, market_id: u64) {
-        Self::require_admin(&env, &admin);
+    /// Sets when an Active market stops taking stakes and cash-outs. Pre-match betting closes at
+    /// `close_time` (at most `start_time`); with `in_play_close` set, it stays open after kickoff
+    /// until that time instead.
+    pub fn set_betting_window(env: Env, caller: Address, market_id: u64, close_time: i64, in_play_close: Option<i64>) -> Result<(), Error> {
+        let config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::MarketCreator)?;
+        Self::extend_instance(&env);
         let market_key = Self::market_key(market_id);
-        let mut market: Market = env.storage().persistent().get(&market_key).expect("market not found");
-        market.reserve_home = 0;
-        market.reserve_draw = 0;
-        market.reserve_away = 0;
-        market.status = MarketStatus::Archived;
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if !Self::is_open(&market) { return Err(Error::MarketNotActive); }
+        if close_time > market.start_time { return Err(Error::InvalidBettingWindow); }
+        if in_play_close.is_some_and(|in_play_close| in_play_close <= market.start_time) { return Err(Error::InvalidBettingWindow); }
+        market.close_time = close_time;
+        market.in_play_close = in_play_close;
+        Self::save_market(&env, &mut market);
+        BettingWindowUpdated { market_id, close_time, in_play_close }.publish(&env);
+        Ok(())
+    }
+
+    /// Chooses how an open market will be settled. A market can't go back to Push once it has
+    /// more stakes than the Push cap allows.
+    pub fn set_settlement_mode(env: Env, caller: Address, market_id: u64, mode: SettlementMode) -> Result<(), Error> {
+        let config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::MarketCreator)?;
+        Self::extend_instance(&env);
+        let market_key = Self::market_key(market_id);
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if !Self::is_open(&market) { return Err(Error::MarketNotActive); }
+        if mode == SettlementMode::Push && market.listed_stakes > MAX_STAKERS_PER_MARKET { return Err(Error::StakerCapReached); }
+        market.settlement = mode;
         env.storage().persistent().set(&market_key, &market);
+        Self::extend_market(&env, &market_key);
+        SettlementModeUpdated { market_id, mode }.publish(&env);
+        Ok(())
     }
 
-    pub fn settle_market(env: Env, admin: Address, market_id: u64, outcome: u32) {
-        Self::require_admin(&env, &admin);
-        if outcome > 2 { panic!("invalid outcome"); }
+    /// Rescales an Active market's reserves to `depth` at the current prices, so later stakes
+    /// move the price more (shallower) or less (deeper).
+    pub fn set_market_depth(env: Env, caller: Address, market_id: u64, depth: i128) -> Result<(), Error> {
+        let config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::OddsManager)?;
+        Self::extend_instance(&env);
+        if depth < MIN_MARKET_DEPTH { return Err(Error::InvalidDepth); }
         let market_key = Self::market_key(market_id);
-        let mut market: Market = env.storage().persistent().get(&market_key).expect("market not found");
-        if market.status != MarketStatus::Active { panic!("market not active"); }
-        let mkstakes_key = Self::market_stakes_key(market_id);
-        let stake_ids: Vec<u64> = env.storage().persistent().get(&mkstakes_key).unwrap_or(Vec::new(&env));
-        let mut total_winning_shares: i128 = 0i128;
-        let stake_ids_len = stake_ids.len();
-        let mut i = 0u32;
-        while i < stake_ids_len {
-            let stake_id: u64 = stake_ids.get(i).unwrap();
-            let stake_key = Self::stake_key(stake_id);
-            let stake: Stake = env.storage().persistent().get(&stake_key).expect("stake not found");
-            if stake.outcome == outcome {
-                total_winning_shares = total_winning_shares.checked_add(stake.amount).expect("overflow winning shares");
-            }
-            i += 1;
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if !Self::is_open(&market) { return Err(Error::MarketNotActive); }
+        // Scaling every reserve by the same factor keeps the prices: for ReserveRatio the total
+        // becomes `depth`, for LMSR `q / b` is unchanged
+        let scale_from = match market.pricing {
+            PricingModel::ReserveRatio => Self::sum(&market.reserves)?,
+            PricingModel::Lmsr => market.depth,
+        };
+        for i in 0..market.reserves.len() {
+            let reserve = market.reserves.get(i).unwrap();
+            market.reserves.set(i, reserve.checked_mul(depth).ok_or(Error::Overflow)?.checked_div(scale_from).ok_or(Error::Overflow)?);
         }
-        let mut total_liq: i128 = env.storage().persistent().get(&key_total_liquidity()).unwrap_or(0i128);
-        let mut total_payouts_needed: i128 = 0;
-        i = 0;
-        while i < stake_ids_len {
-            let stake_id: u64 = stake_ids.get(i).unwrap();
-            let stake_key = Self::stake_key(stake_id);
-            let stake: Stake = env.storage().persistent().get(&stake_key).expect("stake not found");
-            if stake.outcome == outcome {
-                let payout = stake.amount;
-                total_payouts_needed = total_payouts_needed.checked_add(payout).expect("overflow payouts");
+        market.depth = depth;
+        env.storage().persistent().set(&market_key, &market);
+        Self::extend_market(&env, &market_key);
+        MarketDepthUpdated { market_id, depth }.publish(&env);
+        Ok(())
+    }
+
+    /// Archives a market. An Active or Suspended market is voided first so every open stake is refunded.
+    pub fn archive_market(env: Env, caller: Address, market_id: u64) -> Result<(), Error> {
+        let config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::Settler)?;
+        Self::extend_instance(&env);
+        let market_key = Self::market_key(market_id);
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        match market.status {
+            MarketStatus::Active | MarketStatus::Suspended => {
+                Self::start_void(&env, config, market, symbol_short!("archived"))?;
             }
-            i += 1;
+            MarketStatus::Settled => {
+                market.status = MarketStatus::Archived;
+                env.storage().persistent().set(&market_key, &market);
+                Self::extend_market(&env, &market_key);
+                MarketArchived { market_id }.publish(&env);
+            }
+            _ => return Err(Error::MarketNotActive),
         }
-        if total_liq < total_payouts_needed { panic!("insufficient liquidity for payouts"); }
-        i = 0;
-        while i < stake_ids_len {
-            let stake_id: u64 = stake_ids.get(i).unwrap();
+        Ok(())
+    }
+
+    /// Cancels a market and refunds each open stake's cost to its owner. Markets with more than
+    /// MAX_SETTLE_BATCH stakes stay in Voiding until `settle_batch` has refunded the rest, then
+    /// they are Archived.
+    pub fn void_market(env: Env, caller: Address, market_id: u64, reason: Symbol) -> Result<(), Error> {
+        let config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::Settler)?;
+        Self::extend_instance(&env);
+        let market: Market = env.storage().persistent().get(&Self::market_key(market_id)).ok_or(Error::MarketNotFound)?;
+        if !Self::is_open(&market) { return Err(Error::MarketNotActive); }
+        Self::start_void(&env, config, market, reason)
+    }
+
+    /// Records the winning outcome and settles the first batch of stakes. Markets with more
+    /// than MAX_SETTLE_BATCH stakes stay in Settling until `settle_batch` has processed the rest.
+    pub fn settle_market(env: Env, caller: Address, market_id: u64, outcome: u32) -> Result<(), Error> {
+        let mut config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::Settler)?;
+        Self::extend_instance(&env);
+        let market_key = Self::market_key(market_id);
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if !Self::is_open(&market) { return Err(Error::MarketNotActive); }
+        if market.settlement == SettlementMode::Pull { return Err(Error::SettlementModeMismatch); }
+        let winning_shares = market.shares.get(outcome).ok_or(Error::InvalidOutcome)?;
+        market.status = MarketStatus::Settling;
+        market.winning_outcome = Some(outcome);
+        market.settle_cursor = 0;
+        market.total_payout = 0;
+        config.total_liquidity = config.total_liquidity.checked_add(market.escrow).ok_or(Error::Overflow)?;
+        market.escrow = 0;
+        Self::set_market_exposure(&mut config, &mut market, winning_shares)?;
+        SettlementStarted { market_id, outcome }.publish(&env);
+        Self::process_settlement(&env, &mut config, &mut market, MAX_SETTLE_BATCH)?;
+        Ok(())
+    }
+
+    /// Settles (or, for a Voiding market, refunds) up to `limit` more stakes and returns how many
+    /// remain. Permissionless: the result is already fixed, so anyone can push it forward.
+    pub fn settle_batch(env: Env, market_id: u64, limit: u32) -> Result<u32, Error> {
+        Self::extend_instance(&env);
+        if limit == 0 { return Err(Error::InvalidAmount); }
+        let mut config = Self::load_config(&env)?;
+        let market_key = Self::market_key(market_id);
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if market.status != MarketStatus::Settling && market.status != MarketStatus::Voiding { return Err(Error::MarketNotSettling); }
+        Self::process_settlement(&env, &mut config, &mut market, limit.min(MAX_SETTLE_BATCH))
+    }
+
+    /// Pull-based alternative to `settle_market`: records the winning outcome in O(1) and sets
+    /// the winning shares aside from the pool. Stakes stay in place until owners call `claim`.
+    pub fn resolve_market(env: Env, caller: Address, market_id: u64, outcome: u32) -> Result<(), Error> {
+        let mut config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::Settler)?;
+        Self::extend_instance(&env);
+        let market_key = Self::market_key(market_id);
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if !Self::is_open(&market) { return Err(Error::MarketNotActive); }
+        let total_payout = market.shares.get(outcome).ok_or(Error::InvalidOutcome)?;
+        // Stake costs join the pool and the winnings move into escrow until they are claimed
+        let pool_after = config.total_liquidity.checked_add(market.escrow).ok_or(Error::Overflow)?.checked_sub(total_payout).ok_or(Error::Overflow)?;
+        if pool_after < 0 { return Err(Error::InsufficientLiquidity); }
+        config.total_liquidity = pool_after;
+        market.escrow = total_payout;
+        Self::set_market_exposure(&mut config, &mut market, 0)?;
+        Self::save_config(&env, &config);
+        market.reserves = Self::zeroes(&env, market.odds.len());
+        market.status = MarketStatus::Settled;
+        market.winning_outcome = Some(outcome);
+        market.resolved_by_claim = true;
+        market.total_payout = total_payout;
+        Self::save_market(&env, &mut market);
+        MarketSettled { market_id, outcome, total_payout }.publish(&env);
+        Ok(())
+    }
+
+    /// Credits a winning stake of a resolved market to its owner's balance and closes it.
+    pub fn claim(env: Env, user: Address, stake_id: u64) -> Result<i128, Error> {
+        user.require_auth();
+        Self::extend_instance(&env);
+        let stake_key = Self::stake_key(stake_id);
+        let stake: Stake = env.storage().persistent().get(&stake_key).ok_or(Error::StakeNotFound)?;
+        if stake.staker != user { return Err(Error::NotStakeOwner); }
+        let market_key = Self::market_key(stake.market_id);
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if !Self::is_claimable(&market) { return Err(Error::MarketNotSettled); }
+        if market.winning_outcome != Some(stake.outcome) { return Err(Error::NotWinningStake); }
+        market.escrow = market.escrow.checked_sub(stake.amount).ok_or(Error::Overflow)?;
+        env.storage().persistent().set(&market_key, &market);
+        Self::extend_market(&env, &market_key);
+        env.storage().persistent().remove(&stake_key);
+        Self::remove_id(&env, &Self::user_stakes_key(&user, stake.market_id), stake_id);
+        Self::credit_user_balance(&env, &user, stake.amount)?;
+        StakeSettled { market_id: stake.market_id, staker: user, stake_id, payout: stake.amount }.publish(&env);
+        Ok(stake.amount)
+    }
+
+    /// Claims every winning stake the user holds in a resolved market and closes their losing
+    /// stakes in it. Returns the total credited.
+    pub fn claim_all(env: Env, user: Address, market_id: u64) -> Result<i128, Error> {
+        user.require_auth();
+        Self::extend_instance(&env);
+        let market_key = Self::market_key(market_id);
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if !Self::is_claimable(&market) { return Err(Error::MarketNotSettled); }
+        let user_stakes_key = Self::user_stakes_key(&user, market_id);
+        let stake_ids: Vec<u64> = env.storage().persistent().get(&user_stakes_key).unwrap_or(Vec::new(&env));
+        let mut total: i128 = 0;
+        for stake_id in stake_ids.iter() {
             let stake_key = Self::stake_key(stake_id);
-            let stake: Stake = env.storage().persistent().get(&stake_key).expect("stake not found");
+            let stake: Option<Stake> = env.storage().persistent().get(&stake_key);
+            let Some(stake) = stake else { continue };
             env.storage().persistent().remove(&stake_key);
-            if stake.outcome == outcome {
-                let payout = stake.amount; // Fixed $1 per share on settlement
-                Self::credit_user_balance(&env, &stake.staker, payout);
-                total_liq = total_liq.checked_sub(payout).expect("underflow liq");
+            let payout = if market.winning_outcome == Some(stake.outcome) { stake.amount } else { 0 };
+            total = total.checked_add(payout).ok_or(Error::Overflow)?;
+            StakeSettled { market_id, staker: user.clone(), stake_id, payout }.publish(&env);
+        }
+        env.storage().persistent().remove(&user_stakes_key);
+        if total > 0 {
+            market.escrow = market.escrow.checked_sub(total).ok_or(Error::Overflow)?;
+            env.storage().persistent().set(&market_key, &market);
+            Self::extend_market(&env, &market_key);
+            Self::credit_user_balance(&env, &user, total)?;
+        }
+        Ok(total)
+    }
+
+    /// Total the user can currently `claim` from a resolved market.
+    pub fn get_claimable(env: Env, user: Address, market_id: u64) -> Result<i128, Error> {
+        let market: Market = env.storage().persistent().get(&Self::market_key(market_id)).ok_or(Error::MarketNotFound)?;
+        if !Self::is_claimable(&market) { return Ok(0); }
+        let stake_ids: Vec<u64> = env.storage().persistent().get(&Self::user_stakes_key(&user, market_id)).unwrap_or(Vec::new(&env));
+        let mut total: i128 = 0;
+        for stake_id in stake_ids.iter() {
+            let stake: Option<Stake> = env.storage().persistent().get(&Self::stake_key(stake_id));
+            let Some(stake) = stake else { continue };
+            if market.winning_outcome == Some(stake.outcome) {
+                total = total.checked_add(stake.amount).ok_or(Error::Overflow)?;
             }
-            i += 1;
         }
-        env.storage().persistent().set(&key_total_liquidity(), &total_liq);
-        market.reserve_home = 0;
-        market.reserve_draw = 0;
-        market.reserve_away = 0;
-        market.status = MarketStatus::Settled;
-        env.storage().persistent().set(&market_key, &market);
-        let empty_vec: Vec<u64> = Vec::new(&env);
-        env.storage().persistent().set(&mkstakes_key, &empty_vec);
+        Ok(total)
     }
 
-    pub fn provide_liquidity(env: Env, provider: Address, amount: i128) {
-        if amount <= 0 { panic!("amount must be positive"); }
-        let mut lp: i128 = env.storage().persistent().get(&Self::lp_key(&provider)).unwrap_or(0i128);
-        lp = lp.checked_add(amount).expect("overflow lp balance");
+    pub fn provide_liquidity(env: Env, provider: Address, amount: i128) -> Result<(), Error> {
+        provider.require_auth();
+        Self::extend_instance(&env);
+        if amount <= 0 { return Err(Error::InvalidAmount); }
+        let mut config = Self::load_config(&env)?;
+        Self::check_not_paused(&config)?;
+        Self::check_no_result_pending(&env)?;
+        let total_liq = config.total_liquidity;
+        let mut total_shares = config.total_lp_shares;
+        if total_shares > 0 && total_liq <= 0 {
+            // Settlement paid out the whole pool, so the outstanding shares are worth nothing.
+            // Write them off and start a new epoch, in which shares are minted 1:1 again.
+            config.lp_epoch = config.lp_epoch.checked_add(1).ok_or(Error::Overflow)?;
+            LiquidityPoolReset { epoch: config.lp_epoch, written_off_shares: total_shares }.publish(&env);
+            total_shares = 0;
+        }
+        // Shares are minted at the current pool value per share so existing providers keep their PnL.
+        // The first deposit into an empty pool locks a few shares that nobody owns, so the last
+        // provider to leave can't take the share count to zero while the pool still holds a
+        // market's reserve, which the next deposit would otherwise get for free.
+        let (shares, locked) = if total_shares == 0 {
+            (amount.checked_sub(MIN_LOCKED_LP_SHARES).ok_or(Error::Overflow)?, MIN_LOCKED_LP_SHARES)
+        } else {
+            (amount.checked_mul(total_shares).ok_or(Error::Overflow)?.checked_div(total_liq).ok_or(Error::Overflow)?, 0)
+        };
+        if shares <= 0 { return Err(Error::AmountTooSmall); }
+        let mut lp = Self::get_lp_account(&env, &config, &provider);
+        lp.shares = lp.shares.checked_add(shares).ok_or(Error::Overflow)?;
+        lp.deposited = lp.deposited.checked_add(amount).ok_or(Error::Overflow)?;
         env.storage().persistent().set(&Self::lp_key(&provider), &lp);
-        let mut total_liq: i128 = env.storage().persistent().get(&key_total_liquidity()).unwrap_or(0i128);
-        total_liq = total_liq.checked_add(amount).expect("overflow total liq");
-        env.storage().persistent().set(&key_total_liquidity(), &total_liq);
-        // In production integrate actual USDC transfer into this function (transfer_from provider to contract)
+        Self::extend_balance(&env, &Self::lp_key(&provider));
+        config.total_lp_shares = total_shares.checked_add(shares).ok_or(Error::Overflow)?.checked_add(locked).ok_or(Error::Overflow)?;
+        config.total_liquidity = total_liq.checked_add(amount).ok_or(Error::Overflow)?;
+        Self::save_config(&env, &config);
+        Self::token_client(&env, &config).transfer(&provider, env.current_contract_address(), &amount);
+        LiquidityProvided { provider, amount, shares }.publish(&env);
+        Ok(())
     }
 
-    pub fn withdraw_liquidity(env: Env, provider: Address, amount: i128) {
-        if amount <= 0 { panic!("amount must be positive"); }
-        let mut lp: i128 = env.storage().persistent().get(&Self::lp_key(&provider)).unwrap_or(0i128);
-        if lp < amount { panic!("insufficient lp balance"); }
-        lp = lp.checked_sub(amount).expect("underflow lp");
-        env.storage().persistent().set(&Self::lp_key(&provider), &lp);
-        let mut total_liq: i128 = env.storage().persistent().get(&key_total_liquidity()).unwrap_or(0i128);
-        if total_liq < amount { panic!("insufficient total liquidity"); }
-        total_liq = total_liq.checked_sub(amount).expect("underflow total liq");
-        env.storage().persistent().set(&key_total_liquidity(), &total_liq);
-        // In production integrate actual USDC transfer (transfer contract -> provider)
+    pub fn withdraw_liquidity(env: Env, provider: Address, shares: i128) -> Result<(), Error> {
+        provider.require_auth();
+        Self::extend_instance(&env);
+        if shares <= 0 { return Err(Error::InvalidAmount); }
+        let mut config = Self::load_config(&env)?;
+        Self::check_not_paused(&config)?;
+        Self::check_no_result_pending(&env)?;
+        let mut lp = Self::get_lp_account(&env, &config, &provider);
+        if lp.shares < shares { return Err(Error::InsufficientLpBalance); }
+        let total_liq = config.total_liquidity;
+        let total_shares = config.total_lp_shares;
+        // Liquidity reserved against open markets can't leave the pool: shares are redeemed for
+        // their part of the rest, and the reserve stays with the providers who remain
+        let amount = Self::lp_shares_value(shares, total_shares, Self::free_liquidity(&config)?)?;
+        let released_basis = lp.deposited.checked_mul(shares).ok_or(Error::Overflow)?.checked_div(lp.shares).ok_or(Error::Overflow)?;
+        lp.shares = lp.shares.checked_sub(shares).ok_or(Error::Overflow)?;
+        lp.deposited = lp.deposited.checked_sub(released_basis).ok_or(Error::Overflow)?;
+        if lp.shares == 0 {
+            env.storage().persistent().remove(&Self::lp_key(&provider));
+        } else {
+            env.storage().persistent().set(&Self::lp_key(&provider), &lp);
+            Self::extend_balance(&env, &Self::lp_key(&provider));
+        }
+        config.total_lp_shares = total_shares.checked_sub(shares).ok_or(Error::Overflow)?;
+        config.total_liquidity = total_liq.checked_sub(amount).ok_or(Error::Overflow)?;
+        Self::save_config(&env, &config);
+        if amount > 0 {
+            Self::token_client(&env, &config).transfer(&env.current_contract_address(), &provider, &amount);
+        }
+        LiquidityWithdrawn { provider, shares, amount }.publish(&env);
+        Ok(())
+    }
+
+    pub fn total_liquidity(env: Env) -> Result<i128, Error> {
+        Ok(Self::load_config(&env)?.total_liquidity)
+    }
+
+    pub fn total_lp_shares(env: Env) -> Result<i128, Error> {
+        Ok(Self::load_config(&env)?.total_lp_shares)
+    }
+
+    /// Liquidity currently reserved for a market's worst-case payout.
+    pub fn get_market_exposure(env: Env, market_id: u64) -> Result<i128, Error> {
+        let market: Market = env.storage().persistent().get(&Self::market_key(market_id)).ok_or(Error::MarketNotFound)?;
+        Ok(market.exposure)
     }
 
-    pub fn total_liquidity(env: Env) -> i128 {
-        env.storage().persistent().get(&key_total_liquidity()).unwrap_or(0i128)
+    /// Liquidity reserved across all open markets; LP shares are redeemed against the rest.
+    pub fn get_total_exposure(env: Env) -> Result<i128, Error> {
+        Ok(Self::load_config(&env)?.total_exposure)
     }
 
-    pub fn deposit(env: Env, user: Address, amount: i128) {
-        if amount <= 0 { panic!("deposit positive"); }
-        Self::credit_user_balance(&env, &user, amount);
+    pub fn get_lp_position(env: Env, provider: Address) -> Result<LpPosition, Error> {
+        let config = Self::load_config(&env)?;
+        let lp = Self::get_lp_account(&env, &config, &provider);
+        let value = Self::lp_shares_value(lp.shares, config.total_lp_shares, Self::free_liquidity(&config)?)?;
+        Ok(LpPosition {
+            shares: lp.shares,
+            value,
+            pnl: value.checked_sub(lp.deposited).ok_or(Error::Overflow)?,
+        })
     }
 
-    pub fn withdraw(env: Env, user: Address, amount: i128) {
-        if amount <= 0 { panic!("withdraw positive"); }
+    pub fn deposit(env: Env, user: Address, amount: i128) -> Result<(), Error> {
+        user.require_auth();
+        Self::extend_instance(&env);
+        if amount <= 0 { return Err(Error::InvalidAmount); }
+        let config = Self::load_config(&env)?;
+        Self::credit_user_balance(&env, &user, amount)?;
+        Self::token_client(&env, &config).transfer(&user, env.current_contract_address(), &amount);
+        Deposited { user, amount }.publish(&env);
+        Ok(())
+    }
+
+    pub fn withdraw(env: Env, user: Address, amount: i128) -> Result<(), Error> {
+        user.require_auth();
+        Self::extend_instance(&env);
+        if amount <= 0 { return Err(Error::InvalidAmount); }
+        let config = Self::load_config(&env)?;
         let mut bal: i128 = env.storage().persistent().get(&Self::user_key(&user)).unwrap_or(0i128);
-        if bal < amount { panic!("insufficient balance"); }
-        bal = bal.checked_sub(amount).expect("underflow user bal");
+        if bal < amount { return Err(Error::InsufficientBalance); }
+        bal = bal.checked_sub(amount).ok_or(Error::Overflow)?;
         env.storage().persistent().set(&Self::user_key(&user), &bal);
+        Self::extend_balance(&env, &Self::user_key(&user));
+        Self::token_client(&env, &config).transfer(&env.current_contract_address(), &user, &amount);
+        Withdrawn { user, amount }.publish(&env);
+        Ok(())
     }
 
     pub fn get_balance(env: Env, user: Address) -> i128 {
@@ -240,186 +703,713 @@ impl PredictionMarketContract {
         market_id: u64,
         outcome: u32,
         amount: i128,
-    ) {
-        if amount <= 0 { panic!("stake amount positive"); }
-        if outcome > 2 { panic!("invalid outcome"); }
+        min_shares_out: i128,
+        deadline: Option<u64>,
+    ) -> Result<(), Error> {
+        user.require_auth();
+        Self::extend_instance(&env);
+        Self::check_deadline(&env, deadline)?;
+        let mut config = Self::load_config(&env)?;
+        Self::check_not_paused(&config)?;
+        if amount <= 0 { return Err(Error::InvalidAmount); }
         let mut user_bal: i128 = env.storage().persistent().get(&Self::user_key(&user)).unwrap_or(0i128);
-        if user_bal < amount { panic!("insufficient balance"); }
+        if user_bal < amount { return Err(Error::InsufficientBalance); }
+        let mut market: Market = env.storage().persistent().get(&Self::market_key(market_id)).ok_or(Error::MarketNotFound)?;
+        let (shares, avg_price, _) = Self::apply_stake(&env, &mut config, &mut market, outcome, amount)?;
+        if shares < min_shares_out { return Err(Error::SlippageExceeded); }
+
+        user_bal = user_bal.checked_sub(amount).ok_or(Error::Overflow)?;
+        env.storage().persistent().set(&Self::user_key(&user), &user_bal);
+        Self::extend_balance(&env, &Self::user_key(&user));
+        let stake_counter = Self::record_stake(&env, &mut config, &mut market, &user, outcome, shares, avg_price, amount, false)?;
+        Self::save_config(&env, &config);
+        Self::save_market(&env, &mut market);
+        StakePlaced { market_id, staker: user, stake_id: stake_counter, outcome, amount, shares, avg_price }.publish(&env);
+        Ok(())
+    }
+
+    pub fn get_market_stakes(env: Env, market_id: u64) -> Result<Vec<Stake>, Error> {
+        let market: Market = env.storage().persistent().get(&Self::market_key(market_id)).ok_or(Error::MarketNotFound)?;
+        let mut out: Vec<Stake> = Vec::new(&env);
+        for page in 0..market.listed_stakes.div_ceil(STAKE_PAGE_SIZE) {
+            // Settlement removes each page once it has been processed
+            let stake_ids: Vec<u64> = env.storage().persistent().get(&Self::market_stakes_key(market_id, page)).unwrap_or(Vec::new(&env));
+            for stake_id in stake_ids.iter() {
+                // Closed stakes are gone but stay listed
+                if let Some(stake) = env.storage().persistent().get(&Self::stake_key(stake_id)) {
+                    out.push_back(stake);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn get_stake(env: Env, stake_id: u64) -> Result<Stake, Error> {
+        let stake_key = Self::stake_key(stake_id);
+        env.storage().persistent().get(&stake_key).ok_or(Error::StakeNotFound)
+    }
+
+    pub fn get_market(env: Env, market_id: u64) -> Result<Market, Error> {
         let market_key = Self::market_key(market_id);
-        let mut market: Market = env.storage().persistent().get(&market_key).expect("market not found");
-        if market.status != MarketStatus::Active { panic!("market not active"); }
-        if market.staker_count >= MAX_STAKERS_PER_MARKET { panic!("market staker cap reached"); }
-        let reserve = match outcome {
-            0 => market.reserve_home,
-            1 => market.reserve_draw,
-            2 => market.reserve_away,
-            _ => panic!("invalid outcome"),
-        };
-        let total_reserve = market.reserve_home.checked_add(market.reserve_draw).expect("overflow").checked_add(market.reserve_away).expect("overflow");
-        let price_before = Self::calculate_price_from_reserve(reserve, total_reserve);
-        let price_after = Self::calculate_price_from_reserve(
-            reserve.checked_add(amount).expect("add overflow"),
-            total_reserve.checked_add(amount).expect("add overflow")
-        );
-        let avg_price = price_before.checked_add(price_after).expect("add overflow").checked_div(2).expect("div error");
-        let shares = amount.checked_mul(DECIMALS).expect("mul overflow").checked_div(avg_price).expect("div error");
-        match outcome {
-            0 => market.reserve_home = market.reserve_home.checked_add(amount).expect("overflow reserve"),
-            1 => market.reserve_draw = market.reserve_draw.checked_add(amount).expect("overflow reserve"),
-            2 => market.reserve_away = market.reserve_away.checked_add(amount).expect("overflow reserve"),
-            _ => panic!("invalid outcome"),
-        };
+        env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)
+    }
+
+    pub fn get_market_metadata(env: Env, market_id: u64) -> Result<MarketMetadata, Error> {
+        env.storage().persistent().get(&Self::market_metadata_key(market_id)).ok_or(Error::MarketNotFound)
+    }
+
+    pub fn get_staker_count(env: Env, market_id: u64) -> Result<u32, Error> {
+        let m: Market = Self::get_market(env.clone(), market_id)?;
+        Ok(m.staker_count)
+    }
+
+    /// Extends the TTL of a market, its metadata and up to `limit` of its open stakes starting at
+    /// index `start` of its stake list, with the list pages they sit on, and returns how many
+    /// stakes follow the batch. Permissionless so keepers can keep long-running markets alive,
+    /// one batch per call.
+    pub fn bump_market(env: Env, market_id: u64, start: u32, limit: u32) -> Result<u32, Error> {
+        Self::extend_instance(&env);
+        if limit == 0 { return Err(Error::InvalidAmount); }
+        let market_key = Self::market_key(market_id);
+        let market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        Self::extend_market(&env, &market_key);
+        if env.storage().persistent().has(&Self::closing_key()) {
+            Self::extend_market(&env, &Self::closing_key());
+        }
+        let metadata_key = Self::market_metadata_key(market_id);
+        if env.storage().persistent().has(&metadata_key) {
+            Self::extend_market(&env, &metadata_key);
+        }
+        let end = start.saturating_add(limit.min(MAX_BUMP_BATCH)).min(market.listed_stakes);
+        let mut i = start;
+        while i < end {
+            let page = i / STAKE_PAGE_SIZE;
+            let page_end = end.min((page + 1) * STAKE_PAGE_SIZE);
+            let mkstakes_key = Self::market_stakes_key(market_id, page);
+            // Pages already processed by settlement are gone
+            if env.storage().persistent().has(&mkstakes_key) {
+                Self::extend_market(&env, &mkstakes_key);
+                let stake_ids: Vec<u64> = env.storage().persistent().get(&mkstakes_key).unwrap_or(Vec::new(&env));
+                for j in (i % STAKE_PAGE_SIZE)..(page_end - page * STAKE_PAGE_SIZE) {
+                    let stake_key = Self::stake_key(stake_ids.get(j).unwrap());
+                    if env.storage().persistent().has(&stake_key) {
+                        Self::extend_market(&env, &stake_key);
+                    }
+                }
+            }
+            i = page_end;
+        }
+        Ok(market.listed_stakes.saturating_sub(end))
+    }
+
+    pub fn get_admin(env: Env) -> Result<Address, Error> {
+        Ok(Self::load_config(&env)?.admin)
+    }
+
+    pub fn get_pending_admin(env: Env) -> Result<Option<Address>, Error> {
+        Ok(Self::load_config(&env)?.pending_admin)
+    }
+
+    /// First step of an admin transfer: nominates `new_admin`, who must `accept_admin` before
+    /// anything changes. Proposing again replaces the pending nomination.
+    pub fn propose_admin(env: Env, admin: Address, new_admin: Address) -> Result<(), Error> {
+        let mut config = Self::load_config(&env)?;
+        Self::require_admin(&config, &admin)?;
+        Self::extend_instance(&env);
+        config.pending_admin = Some(new_admin.clone());
+        Self::save_config(&env, &config);
+        AdminTransferProposed { admin, new_admin }.publish(&env);
+        Ok(())
+    }
+
+    /// Second step: the nominated address signs to take over as admin.
+    pub fn accept_admin(env: Env, new_admin: Address) -> Result<(), Error> {
+        let mut config = Self::load_config(&env)?;
+        if config.pending_admin.as_ref() != Some(&new_admin) { return Err(Error::NoPendingAdmin); }
+        new_admin.require_auth();
+        Self::extend_instance(&env);
+        let previous_admin = config.admin.clone();
+        config.admin = new_admin.clone();
+        config.pending_admin = None;
+        Self::save_config(&env, &config);
+        AdminTransferred { previous_admin, new_admin }.publish(&env);
+        Ok(())
+    }
+
+    pub fn cancel_admin_transfer(env: Env, admin: Address) -> Result<(), Error> {
+        let mut config = Self::load_config(&env)?;
+        Self::require_admin(&config, &admin)?;
+        Self::extend_instance(&env);
+        let new_admin = config.pending_admin.take().ok_or(Error::NoPendingAdmin)?;
+        Self::save_config(&env, &config);
+        AdminTransferCancelled { admin, new_admin }.publish(&env);
+        Ok(())
+    }
+
+    pub fn get_token(env: Env) -> Result<Address, Error> {
+        Ok(Self::load_config(&env)?.token)
+    }
+
+    pub fn get_config(env: Env) -> Result<Config, Error> {
+        Self::load_config(&env)
+    }
+
+    pub fn grant_role(env: Env, admin: Address, role: Role, account: Address) -> Result<(), Error> {
+        let config = Self::load_config(&env)?;
+        Self::require_admin(&config, &admin)?;
+        Self::extend_instance(&env);
+        let role_key = Self::role_key(role, &account);
+        env.storage().persistent().set(&role_key, &true);
+        Self::extend_role(&env, &role_key);
+        RoleGranted { role, account }.publish(&env);
+        Ok(())
+    }
+
+    pub fn revoke_role(env: Env, admin: Address, role: Role, account: Address) -> Result<(), Error> {
+        let config = Self::load_config(&env)?;
+        Self::require_admin(&config, &admin)?;
+        Self::extend_instance(&env);
+        env.storage().persistent().remove(&Self::role_key(role, &account));
+        RoleRevoked { role, account }.publish(&env);
+        Ok(())
+    }
+
+    /// Whether `account` may act as `role`, either through a grant or by being the admin.
+    pub fn has_role(env: Env, role: Role, account: Address) -> Result<bool, Error> {
+        let config = Self::load_config(&env)?;
+        Ok(config.admin == account || env.storage().persistent().has(&Self::role_key(role, &account)))
+    }
+
+    /// Emergency stop: halts stakes, cash-outs, complete sets and liquidity changes across every
+    /// market. Deposits, withdrawals of free balances, settlement and claims keep working.
+    pub fn pause(env: Env, caller: Address) -> Result<(), Error> {
+        let mut config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::Guardian)?;
+        Self::extend_instance(&env);
+        config.paused = true;
+        Self::save_config(&env, &config);
+        ContractPaused { by: caller }.publish(&env);
+        Ok(())
+    }
+
+    pub fn unpause(env: Env, caller: Address) -> Result<(), Error> {
+        let mut config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::Guardian)?;
+        Self::extend_instance(&env);
+        config.paused = false;
+        Self::save_config(&env, &config);
+        ContractUnpaused { by: caller }.publish(&env);
+        Ok(())
+    }
+
+    /// Halts trading in one Active market. Its odds can still be updated, and it can still be
+    /// settled or voided.
+    pub fn suspend_market(env: Env, caller: Address, market_id: u64) -> Result<(), Error> {
+        let config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::Guardian)?;
+        Self::extend_instance(&env);
+        let market_key = Self::market_key(market_id);
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if market.status != MarketStatus::Active { return Err(Error::MarketNotActive); }
+        market.status = MarketStatus::Suspended;
+        env.storage().persistent().set(&market_key, &market);
+        Self::extend_market(&env, &market_key);
+        MarketSuspended { market_id, by: caller }.publish(&env);
+        Ok(())
+    }
+
+    pub fn resume_market(env: Env, caller: Address, market_id: u64) -> Result<(), Error> {
+        let config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::Guardian)?;
+        Self::extend_instance(&env);
+        let market_key = Self::market_key(market_id);
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if market.status != MarketStatus::Suspended { return Err(Error::MarketNotActive); }
+        market.status = MarketStatus::Active;
+        env.storage().persistent().set(&market_key, &market);
+        Self::extend_market(&env, &market_key);
+        MarketResumed { market_id, by: caller }.publish(&env);
+        Ok(())
+    }
+
+    pub fn set_cashout_fee(env: Env, caller: Address, fee_percent: i128) -> Result<(), Error> {
+        let mut config = Self::load_config(&env)?;
+        Self::require_role(&env, &config, &caller, Role::Treasurer)?;
+        Self::extend_instance(&env);
+        if !(0..=MAX_CASHOUT_FEE_PERCENT).contains(&fee_percent) { return Err(Error::InvalidFee); }
+        let old = config.cashout_fee_percent;
+        config.cashout_fee_percent = fee_percent;
+        Self::save_config(&env, &config);
+        CashoutFeeUpdated { old, new: fee_percent, by: caller }.publish(&env);
+        Ok(())
+    }
+
+    /// Current price of each outcome, in the order of the market's outcomes.
+    pub fn get_current_odds(env: Env, market_id: u64) -> Result<Vec<i128>, Error> {
+        let market_key = Self::market_key(market_id);
+        let market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        Self::current_prices(&market)
+    }
+
+    /// Fill `place_stake` would give right now: (shares, avg_price, price_after).
+    /// Fails the way `place_stake` would, except for the caller's balance.
+    pub fn quote_stake(env: Env, market_id: u64, outcome: u32, amount: i128) -> Result<(i128, i128, i128), Error> {
+        let mut config = Self::load_config(&env)?;
+        Self::check_not_paused(&config)?;
+        if amount <= 0 { return Err(Error::InvalidAmount); }
+        let mut market: Market = env.storage().persistent().get(&Self::market_key(market_id)).ok_or(Error::MarketNotFound)?;
+        Self::apply_stake(&env, &mut config, &mut market, outcome, amount)
+    }
+
+    /// Payout `cash_out` would give right now: (gross, fee, net).
+    /// Fails the way `cash_out` would.
+    pub fn quote_cash_out(env: Env, stake_id: u64) -> Result<(i128, i128, i128), Error> {
+        let mut config = Self::load_config(&env)?;
+        Self::check_not_paused(&config)?;
+        let stake: Stake = env.storage().persistent().get(&Self::stake_key(stake_id)).ok_or(Error::StakeNotFound)?;
+        let mut market: Market = env.storage().persistent().get(&Self::market_key(stake.market_id)).ok_or(Error::MarketNotFound)?;
+        Self::apply_cash_out(&env, &mut config, &mut market, &stake)
+    }
+
+    pub fn cash_out(env: Env, user: Address, stake_id: u64, min_payout: i128, deadline: Option<u64>) -> Result<(), Error> {
+        user.require_auth();
+        Self::extend_instance(&env);
+        Self::check_deadline(&env, deadline)?;
+        let mut config = Self::load_config(&env)?;
+        Self::check_not_paused(&config)?;
+        let stake_key = Self::stake_key(stake_id);
+        let stake: Stake = env.storage().persistent().get(&stake_key).ok_or(Error::StakeNotFound)?;
+        if stake.staker != user { return Err(Error::NotStakeOwner); }
+        let mut market: Market = env.storage().persistent().get(&Self::market_key(stake.market_id)).ok_or(Error::MarketNotFound)?;
+        let (payout_before_fee, fee, payout_after_fee) = Self::apply_cash_out(&env, &mut config, &mut market, &stake)?;
+        if payout_after_fee < min_payout { return Err(Error::SlippageExceeded); }
+        Self::save_config(&env, &config);
+
+        Self::credit_user_balance(&env, &user, payout_after_fee)?;
+        env.storage().persistent().remove(&stake_key);
+        Self::remove_id(&env, &Self::user_stakes_key(&user, stake.market_id), stake_id);
+        market.staker_count = market.staker_count.saturating_sub(1);
+        Self::save_market(&env, &mut market);
+        CashedOut {
+            market_id: stake.market_id,
+            staker: user,
+            stake_id,
+            gross_payout: payout_before_fee,
+            fee,
+            net_payout: payout_after_fee,
+        }.publish(&env);
+        Ok(())
+    }
+
+    fn start_void(env: &Env, mut config: Config, mut market: Market, reason: Symbol) -> Result<(), Error> {
+        market.reserves = Self::zeroes(env, market.odds.len());
+        market.shares = Self::zeroes(env, market.odds.len());
+        Self::set_market_exposure(&mut config, &mut market, 0)?;
+        market.status = MarketStatus::Voiding;
+        market.void_reason = Some(reason.clone());
+        market.settle_cursor = 0;
+        market.total_payout = 0;
+        MarketVoided { market_id: market.id, reason }.publish(env);
+        Self::process_settlement(env, &mut config, &mut market, MAX_SETTLE_BATCH)?;
+        Ok(())
+    }
+
+    /// Pays `amount` for `amount` shares of every outcome, recorded as one stake per outcome.
+    /// Exactly one outcome wins, so a complete set is always worth $1 per share and does not
+    /// move prices. Returns the new stake ids in outcome order.
+    pub fn mint_complete_set(env: Env, user: Address, market_id: u64, amount: i128) -> Result<Vec<u64>, Error> {
+        user.require_auth();
+        Self::extend_instance(&env);
+        let mut config = Self::load_config(&env)?;
+        Self::check_not_paused(&config)?;
+        if amount <= 0 { return Err(Error::InvalidAmount); }
+        let mut user_bal: i128 = env.storage().persistent().get(&Self::user_key(&user)).unwrap_or(0i128);
+        if user_bal < amount { return Err(Error::InsufficientBalance); }
+        let market_key = Self::market_key(market_id);
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if market.status != MarketStatus::Active { return Err(Error::MarketNotActive); }
+        Self::check_betting_open(&env, &market)?;
+        let outcome_count = market.odds.len();
+        if market.settlement == SettlementMode::Push && market.listed_stakes.saturating_add(outcome_count) > MAX_STAKERS_PER_MARKET {
+            return Err(Error::StakerCapReached);
+        }
+        // Split the cost evenly so each leg has a refundable cost basis if the market is voided;
+        // the last leg takes the rounding remainder
+        let leg_cost = amount / outcome_count as i128;
+        let price = DECIMALS / outcome_count as i128;
+        for outcome in 0..outcome_count {
+            Self::add_at(&mut market.shares, outcome, amount)?;
+        }
+        Self::shift_reserves(&mut market, amount)?;
+        market.escrow = market.escrow.checked_add(amount).ok_or(Error::Overflow)?;
 
-        user_bal = user_bal.checked_sub(amount).expect("underflow user bal");
+        user_bal = user_bal.checked_sub(amount).ok_or(Error::Overflow)?;
         env.storage().persistent().set(&Self::user_key(&user), &user_bal);
-        // Stakes do NOT add to LP pool - they are bets against the pool
-        // LP pool only grows from provide_liquidity() and losing bets
-        let mut stake_counter: u64 = env.storage().persistent().get(&key_stake_counter()).unwrap_or(0u64);
-        stake_counter += 1;
-        env.storage().persistent().set(&key_stake_counter(), &stake_counter);
+        Self::extend_balance(&env, &Self::user_key(&user));
+        let mut stake_ids: Vec<u64> = Vec::new(&env);
+        for outcome in 0..outcome_count {
+            let cost = if outcome + 1 == outcome_count { amount - leg_cost * (outcome_count as i128 - 1) } else { leg_cost };
+            let stake_id = Self::record_stake(&env, &mut config, &mut market, &user, outcome, amount, price, cost, true)?;
+            stake_ids.push_back(stake_id);
+        }
+        Self::save_config(&env, &config);
+        env.storage().persistent().set(&market_key, &market);
+        Self::extend_market(&env, &market_key);
+        CompleteSetMinted { market_id, user, amount, stake_ids: stake_ids.clone() }.publish(&env);
+        Ok(stake_ids)
+    }
+
+    /// Burns `amount` shares from one stake per outcome of the same Active market and pays $1
+    /// per set back to the user's balance. Stakes left with no shares are closed. On
+    /// ReserveRatio markets every stake must come from `mint_complete_set`.
+    pub fn redeem_complete_set(env: Env, user: Address, stake_ids: Vec<u64>, amount: i128) -> Result<(), Error> {
+        user.require_auth();
+        Self::extend_instance(&env);
+        let mut config = Self::load_config(&env)?;
+        Self::check_not_paused(&config)?;
+        if amount <= 0 { return Err(Error::InvalidAmount); }
+        let mut stakes: Vec<Stake> = Vec::new(&env);
+        for stake_id in stake_ids.iter() {
+            let stake: Stake = env.storage().persistent().get(&Self::stake_key(stake_id)).ok_or(Error::StakeNotFound)?;
+            if stake.staker != user { return Err(Error::NotStakeOwner); }
+            if stake.amount < amount { return Err(Error::InsufficientShares); }
+            stakes.push_back(stake);
+        }
+        let market_id = stakes.get(0).ok_or(Error::IncompleteSet)?.market_id;
+        let market_key = Self::market_key(market_id);
+        let mut market: Market = env.storage().persistent().get(&market_key).ok_or(Error::MarketNotFound)?;
+        if market.status != MarketStatus::Active { return Err(Error::MarketNotActive); }
+        Self::check_betting_open(&env, &market)?;
+        // One stake per outcome: as many stakes as outcomes, all in this market, no outcome twice
+        if stakes.len() != market.odds.len() { return Err(Error::IncompleteSet); }
+        let mut covered: u64 = 0;
+        for stake in stakes.iter() {
+            let bit = 1u64 << stake.outcome;
+            if stake.market_id != market_id || covered & bit != 0 { return Err(Error::IncompleteSet); }
+            covered |= bit;
+        }
+        // A reserve-ratio fill is priced between the prices before and after it, below its curve,
+        // so legs bought one by one can cost less than the $1 a set redeems for. LMSR costs are
+        // path-independent, so a set bought in legs there never costs less than $1
+        if market.pricing == PricingModel::ReserveRatio && stakes.iter().any(|stake| !stake.minted) { return Err(Error::UnmintedSet); }
+
+        // Burned shares release their share of each stake's cost from escrow; the pool covers
+        // the difference to the $1 per set paid out (or keeps it, if the legs cost more)
+        let mut released_cost: i128 = 0;
+        for mut stake in stakes.iter() {
+            let cost = stake.cost.checked_mul(amount).ok_or(Error::Overflow)?.checked_div(stake.amount).ok_or(Error::Overflow)?;
+            released_cost = released_cost.checked_add(cost).ok_or(Error::Overflow)?;
+            stake.amount = stake.amount.checked_sub(amount).ok_or(Error::Overflow)?;
+            stake.cost = stake.cost.checked_sub(cost).ok_or(Error::Overflow)?;
+            let stake_key = Self::stake_key(stake.id);
+            if stake.amount == 0 {
+                env.storage().persistent().remove(&stake_key);
+                Self::remove_id(&env, &Self::user_stakes_key(&user, market_id), stake.id);
+                market.staker_count = market.staker_count.saturating_sub(1);
+            } else {
+                env.storage().persistent().set(&stake_key, &stake);
+                Self::extend_market(&env, &stake_key);
+            }
+        }
+        for outcome in 0..market.odds.len() {
+            Self::add_at(&mut market.shares, outcome, amount.checked_neg().ok_or(Error::Overflow)?)?;
+        }
+        Self::shift_reserves(&mut market, amount.checked_neg().ok_or(Error::Overflow)?)?;
+        market.escrow = market.escrow.checked_sub(released_cost).ok_or(Error::Overflow)?;
+        let pool_after = config.total_liquidity.checked_add(released_cost).ok_or(Error::Overflow)?.checked_sub(amount).ok_or(Error::Overflow)?;
+        if pool_after < 0 { return Err(Error::InsufficientLiquidity); }
+        config.total_liquidity = pool_after;
+        let exposure = Self::active_exposure(&market)?;
+        Self::set_market_exposure(&mut config, &mut market, exposure)?;
+        if config.total_exposure > config.total_liquidity { return Err(Error::ExposureLimitExceeded); }
+        Self::save_config(&env, &config);
+        Self::save_market(&env, &mut market);
+        Self::credit_user_balance(&env, &user, amount)?;
+        CompleteSetRedeemed { market_id, user, amount }.publish(&env);
+        Ok(())
+    }
+
+    /// Stores a new stake and indexes it under its market and under its owner in that market. The
+    /// market list is paged and only its last page is rewritten; closed stakes stay listed and
+    /// readers skip them. The caller saves the config and market.
+    fn record_stake(env: &Env, config: &mut Config, market: &mut Market, user: &Address, outcome: u32, shares: i128, price: i128, cost: i128, minted: bool) -> Result<u64, Error> {
+        config.stake_counter += 1;
+        let stake_counter = config.stake_counter;
         let stake = Stake {
             id: stake_counter,
             staker: user.clone(),
-            market_id,
+            market_id: market.id,
             outcome,
             amount: shares, // store shares received
-            price: avg_price, // store average price paid
+            price, // store average price paid
+            cost,
+            minted,
         };
         let stake_key = Self::stake_key(stake_counter);
         env.storage().persistent().set(&stake_key, &stake);
-        let mkstakes_key = Self::market_stakes_key(market_id);
-        let mut stake_ids: Vec<u64> = env.storage().persistent().get(&mkstakes_key).unwrap_or(Vec::new(&env));
+        Self::extend_market(env, &stake_key);
+        let mkstakes_key = Self::market_stakes_key(market.id, market.listed_stakes / STAKE_PAGE_SIZE);
+        let mut stake_ids: Vec<u64> = env.storage().persistent().get(&mkstakes_key).unwrap_or(Vec::new(env));
         stake_ids.push_back(stake_counter);
         env.storage().persistent().set(&mkstakes_key, &stake_ids);
-        market.staker_count = market.staker_count.checked_add(1).expect("overflow staker count");
-        env.storage().persistent().set(&market_key, &market);
+        Self::extend_market(env, &mkstakes_key);
+        market.listed_stakes = market.listed_stakes.checked_add(1).ok_or(Error::Overflow)?;
+        let user_stakes_key = Self::user_stakes_key(user, market.id);
+        let mut user_stake_ids: Vec<u64> = env.storage().persistent().get(&user_stakes_key).unwrap_or(Vec::new(env));
+        user_stake_ids.push_back(stake_counter);
+        env.storage().persistent().set(&user_stakes_key, &user_stake_ids);
+        Self::extend_market(env, &user_stakes_key);
+        market.staker_count = market.staker_count.checked_add(1).ok_or(Error::Overflow)?;
+        Ok(stake_counter)
     }
 
-    pub fn get_market_stakes(env: Env, market_id: u64) -> Vec<Stake> {
-        let mkstakes_key = Self::market_stakes_key(market_id);
-        let stake_ids: Vec<u64> = env.storage().persistent().get(&mkstakes_key).unwrap_or(Vec::new(&env));
-        let mut out: Vec<Stake> = Vec::new(&env);
-        let stake_ids_len = stake_ids.len();
-        let mut i = 0u32;
-        while i < stake_ids_len {
-            let stake_id: u64 = stake_ids.get(i).unwrap();
+    fn process_settlement(env: &Env, config: &mut Config, market: &mut Market, limit: u32) -> Result<u32, Error> {
+        let voiding = market.status == MarketStatus::Voiding;
+        let outcome = if voiding { 0 } else { market.winning_outcome.ok_or(Error::MarketNotSettling)? };
+        let end = market.settle_cursor.saturating_add(limit).min(market.listed_stakes);
+        let mut batch_payout: i128 = 0;
+        let mut page: Option<(u32, Vec<u64>)> = None;
+        let mut i = market.settle_cursor;
+        while i < end {
+            let page_no = i / STAKE_PAGE_SIZE;
+            if page.as_ref().is_none_or(|(loaded, _)| *loaded != page_no) {
+                let stake_ids: Vec<u64> = env.storage().persistent().get(&Self::market_stakes_key(market.id, page_no)).unwrap_or(Vec::new(env));
+                page = Some((page_no, stake_ids));
+            }
+            let stake_id: u64 = page.as_ref().unwrap().1.get(i % STAKE_PAGE_SIZE).unwrap();
+            // A page is done with once its last stake is processed
+            if (i + 1).is_multiple_of(STAKE_PAGE_SIZE) || i + 1 == market.listed_stakes {
+                env.storage().persistent().remove(&Self::market_stakes_key(market.id, page_no));
+            }
             let stake_key = Self::stake_key(stake_id);
-            let stake: Stake = env.storage().persistent().get(&stake_key).expect("stake not found");
-            out.push_back(stake);
+            // Stakes cashed out or redeemed before the market closed stay listed
+            let stake: Option<Stake> = env.storage().persistent().get(&stake_key);
+            let Some(stake) = stake else {
+                i += 1;
+                continue;
+            };
+            env.storage().persistent().remove(&stake_key);
+            if voiding {
+                // Refunds come out of the stake's own cost in escrow, not the pool
+                market.escrow = market.escrow.checked_sub(stake.cost).ok_or(Error::Overflow)?;
+                Self::credit_user_balance(env, &stake.staker, stake.cost)?;
+                batch_payout = batch_payout.checked_add(stake.cost).ok_or(Error::Overflow)?;
+                StakeRefunded { market_id: market.id, staker: stake.staker, stake_id, refund: stake.cost }.publish(env);
+                i += 1;
+                continue;
+            }
+            let mut payout = 0i128;
+            if stake.outcome == outcome {
+                payout = stake.amount; // Fixed $1 per share on settlement
+                Self::credit_user_balance(env, &stake.staker, payout)?;
+                batch_payout = batch_payout.checked_add(payout).ok_or(Error::Overflow)?;
+            }
+            StakeSettled { market_id: market.id, staker: stake.staker, stake_id, payout }.publish(env);
             i += 1;
         }
-        out
+        if !voiding {
+            if config.total_liquidity < batch_payout { return Err(Error::InsufficientLiquidity); }
+            config.total_liquidity = config.total_liquidity.checked_sub(batch_payout).ok_or(Error::Overflow)?;
+            let unpaid = market.exposure.checked_sub(batch_payout).ok_or(Error::Overflow)?;
+            Self::set_market_exposure(config, market, unpaid)?;
+        }
+        Self::save_config(env, config);
+        market.total_payout = market.total_payout.checked_add(batch_payout).ok_or(Error::Overflow)?;
+        market.settle_cursor = end;
+        let remaining = market.listed_stakes - end;
+        if remaining == 0 {
+            market.reserves = Self::zeroes(env, market.odds.len());
+            if voiding {
+                config.total_liquidity = config.total_liquidity.checked_add(market.escrow).ok_or(Error::Overflow)?;
+                market.escrow = 0;
+                Self::save_config(env, config);
+                market.status = MarketStatus::Archived;
+                MarketArchived { market_id: market.id }.publish(env);
+            } else {
+                market.status = MarketStatus::Settled;
+                MarketSettled { market_id: market.id, outcome, total_payout: market.total_payout }.publish(env);
+            }
+        } else {
+            Self::extend_market(env, &Self::market_stakes_key(market.id, end / STAKE_PAGE_SIZE));
+        }
+        Self::save_market(env, market);
+        Ok(remaining)
     }
 
-    pub fn get_stake(env: Env, stake_id: u64) -> Stake {
-        let stake_key = Self::stake_key(stake_id);
-        env.storage().persistent().get(&stake_key).expect("stake not found")
+    /// Worst-case pool loss of an Active market: the largest payout on any outcome, less the
+    /// escrowed stake costs that join the pool when it settles.
+    fn active_exposure(market: &Market) -> Result<i128, Error> {
+        let max_liability = market.shares.iter().max().unwrap_or(0);
+        Ok(max_liability.checked_sub(market.escrow).ok_or(Error::Overflow)?.max(0))
     }
 
-    pub fn get_market(env: Env, market_id: u64) -> Market {
-        let market_key = Self::market_key(market_id);
-        env.storage().persistent().get(&market_key).expect("market not found")
+    fn set_market_exposure(config: &mut Config, market: &mut Market, exposure: i128) -> Result<(), Error> {
+        config.total_exposure = config.total_exposure
+            .checked_sub(market.exposure).ok_or(Error::Overflow)?
+            .checked_add(exposure).ok_or(Error::Overflow)?;
+        market.exposure = exposure;
+        Ok(())
     }
 
-    pub fn get_staker_count(env: Env, market_id: u64) -> u32 {
-        let m: Market = Self::get_market(env.clone(), market_id);
-        m.staker_count
+    /// `deadline` is a ledger timestamp after which the call is rejected.
+    fn check_deadline(env: &Env, deadline: Option<u64>) -> Result<(), Error> {
+        match deadline {
+            Some(deadline) if env.ledger().timestamp() > deadline => Err(Error::DeadlinePassed),
+            _ => Ok(()),
+        }
     }
 
-    pub fn get_admin(env: Env) -> Address {
-        env.storage().persistent().get(&key_admin()).expect("admin not set")
+    /// Stakes, cash-outs and complete sets trade against the market's prices and reserves, so
+    /// they stop once betting closes: at `close_time`, or at `in_play_close` for markets that
+    /// stay open in-play.
+    fn check_betting_open(env: &Env, market: &Market) -> Result<(), Error> {
+        let now = env.ledger().timestamp() as i64;
+        let close = market.in_play_close.unwrap_or(market.close_time);
+        if now >= close { return Err(Error::BettingClosed); }
+        Ok(())
     }
 
-    pub fn get_current_odds(env: Env, market_id: u64) -> (i128, i128, i128) {
-        let market_key = Self::market_key(market_id);
-        let market: Market = env.storage().persistent().get(&market_key).expect("market not found");
-        let total_reserve = market.reserve_home.checked_add(market.reserve_draw).expect("overflow").checked_add(market.reserve_away).expect("overflow");
-        if total_reserve == 0 {
-            return (market.odds_home, market.odds_draw, market.odds_away);
+    fn load_config(env: &Env) -> Result<Config, Error> {
+        env.storage().instance().get(&key_config()).ok_or(Error::NotInitialized)
+    }
+
+    fn save_config(env: &Env, config: &Config) {
+        env.storage().instance().set(&key_config(), config);
+    }
+
+    fn require_admin(config: &Config, who: &Address) -> Result<(), Error> {
+        if config.admin != *who { return Err(Error::Unauthorized); }
+        who.require_auth();
+        Ok(())
+    }
+
+    fn require_role(env: &Env, config: &Config, who: &Address, role: Role) -> Result<(), Error> {
+        if config.admin != *who {
+            let role_key = Self::role_key(role, who);
+            if !env.storage().persistent().has(&role_key) { return Err(Error::Unauthorized); }
+            Self::extend_role(env, &role_key);
         }
-        let odds_home = Self::calculate_price_from_reserve(market.reserve_home, total_reserve);
-        let odds_draw = Self::calculate_price_from_reserve(market.reserve_draw, total_reserve);
-        let odds_away = Self::calculate_price_from_reserve(market.reserve_away, total_reserve);
-        (odds_home, odds_draw, odds_away)
+        who.require_auth();
+        Ok(())
     }
 
-    pub fn cash_out(env: Env, user: Address, stake_id: u64) {
-        let stake_key = Self::stake_key(stake_id);
-        let stake: Stake = env.storage().persistent().get(&stake_key).expect("stake not found");
-        if stake.staker != user { panic!("unauthorized: not stake owner"); }
-        let market_key = Self::market_key(stake.market_id);
-        let mut market: Market = env.storage().persistent().get(&market_key).expect("market not found");
-        if market.status != MarketStatus::Active { panic!("market not active"); }
-        let reserve = match stake.outcome {
-            0 => market.reserve_home,
-            1 => market.reserve_draw,
-            2 => market.reserve_away,
-            _ => panic!("invalid outcome"),
-        };
-        let shares = stake.amount;
-        let total_reserve = market.reserve_home.checked_add(market.reserve_draw).expect("overflow").checked_add(market.reserve_away).expect("overflow");
-        let price_before_exit = Self::calculate_price_from_reserve(reserve, total_reserve);
-        let estimated_payout = shares.checked_mul(price_before_exit).expect("mul overflow").checked_div(DECIMALS).expect("div error");
-        let price_after_exit = if reserve <= estimated_payout {
-            0 // Would drain the reserve
-        } else {
-            Self::calculate_price_from_reserve(
-                reserve.checked_sub(estimated_payout).expect("sub error"),
-                total_reserve.checked_sub(estimated_payout).expect("sub error")
-            )
-        };
-        let avg_exit_price = price_before_exit.checked_add(price_after_exit).expect("add overflow").checked_div(2).expect("div error");
-        let payout_before_fee = shares.checked_mul(avg_exit_price).expect("mul overflow").checked_div(DECIMALS).expect("div error");
-        let fee = payout_before_fee.checked_mul(CASHOUT_FEE_PERCENT).expect("mul overflow").checked_div(100).expect("div error");
-        let payout_after_fee = payout_before_fee.checked_sub(fee).expect("underflow payout");
-        match stake.outcome {
-            0 => market.reserve_home = market.reserve_home.checked_sub(payout_before_fee).expect("underflow reserve"),
-            1 => market.reserve_draw = market.reserve_draw.checked_sub(payout_before_fee).expect("underflow reserve"),
-            2 => market.reserve_away = market.reserve_away.checked_sub(payout_before_fee).expect("underflow reserve"),
-            _ => panic!("invalid outcome"),
-        };
+    fn check_not_paused(config: &Config) -> Result<(), Error> {
+        if config.paused { return Err(Error::ContractPaused); }
+        Ok(())
+    }
 
-        Self::credit_user_balance(&env, &user, payout_after_fee);
-        env.storage().persistent().remove(&stake_key);
-        let mkstakes_key = Self::market_stakes_key(stake.market_id);
-        let stake_ids: Vec<u64> = env.storage().persistent().get(&mkstakes_key).unwrap_or(Vec::new(&env));
-        let mut new_stake_ids: Vec<u64> = Vec::new(&env);
-        let mut i = 0u32;
-        while i < stake_ids.len() {
-            let id = stake_ids.get(i).unwrap();
-            if id != stake_id {
-                new_stake_ids.push_back(id);
-            }
-            i += 1;
+    /// Not yet settled or voided: Active, or Suspended and waiting to resume.
+    fn is_open(market: &Market) -> bool {
+        matches!(market.status, MarketStatus::Active | MarketStatus::Suspended)
+    }
+
+    /// Resolved with `resolve_market`. A market settled by `settle_market` pays its winners itself,
+    /// and claiming while that is still running would pull stakes out from under `settle_batch`.
+    fn is_claimable(market: &Market) -> bool {
+        market.resolved_by_claim && matches!(market.status, MarketStatus::Settled | MarketStatus::Archived)
+    }
+
+    fn validate_odds(odds: &Vec<i128>) -> Result<(), Error> {
+        if odds.iter().any(|odd| odd < MIN_PRICE) {
+            return Err(Error::OddsBelowMinimum);
         }
-        env.storage().persistent().set(&mkstakes_key, &new_stake_ids);
-        market.staker_count = market.staker_count.checked_sub(1).unwrap_or(0);
-        env.storage().persistent().set(&market_key, &market);
+        if Self::sum(odds)? != TOTAL_PRICE_SUM { return Err(Error::InvalidOddsSum); }
+        Ok(())
+    }
+
+    fn validate_metadata(metadata: &MarketMetadata, outcome_count: u32) -> Result<(), Error> {
+        if metadata.outcome_labels.len() != outcome_count { return Err(Error::InvalidOutcomeCount); }
+        if metadata.title.is_empty() { return Err(Error::InvalidMetadata); }
+        let fields = [&metadata.title, &metadata.home_team, &metadata.away_team, &metadata.competition, &metadata.venue, &metadata.fixture_id, &metadata.image_uri];
+        if fields.iter().any(|field| field.len() > MAX_METADATA_LEN) || metadata.outcome_labels.iter().any(|label| label.is_empty() || label.len() > MAX_METADATA_LEN) {
+            return Err(Error::InvalidMetadata);
+        }
+        Ok(())
     }
 
-    fn require_admin(env: &Env, who: &Address) {
-        let admin: Address = env.storage().persistent().get(&key_admin()).expect("admin not set");
-        if admin != *who { panic!("unauthorized: admin only"); }
+    fn sum(values: &Vec<i128>) -> Result<i128, Error> {
+        values.iter().try_fold(0i128, |total, value| total.checked_add(value).ok_or(Error::Overflow))
     }
 
-    fn validate_odds(odds_home: i128, odds_draw: i128, odds_away: i128) {
-        if odds_home < MIN_PRICE || odds_draw < MIN_PRICE || odds_away < MIN_PRICE {
-            panic!("odds below minimum");
+    fn zeroes(env: &Env, len: u32) -> Vec<i128> {
+        let mut values = Vec::new(env);
+        for _ in 0..len {
+            values.push_back(0);
         }
-        let sum = odds_home.checked_add(odds_draw).and_then(|s| s.checked_add(odds_away)).expect("overflow sum");
-        if sum != TOTAL_PRICE_SUM { panic!("odds must sum to $0.99 (in 6 decimals)"); }
+        values
+    }
+
+    /// Adds `delta` to the entry for `outcome`, failing on an unknown outcome.
+    fn add_at(values: &mut Vec<i128>, outcome: u32, delta: i128) -> Result<(), Error> {
+        let value = values.get(outcome).ok_or(Error::InvalidOutcome)?;
+        values.set(outcome, value.checked_add(delta).ok_or(Error::Overflow)?);
+        Ok(())
+    }
+
+    fn extend_instance(env: &Env) {
+        // Also covers the Config entry, which lives in instance storage
+        env.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    }
+
+    fn extend_balance<K: IntoVal<Env, Val>>(env: &Env, key: &K) {
+        env.storage().persistent().extend_ttl(key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+
+    fn extend_market<K: IntoVal<Env, Val>>(env: &Env, key: &K) {
+        env.storage().persistent().extend_ttl(key, MARKET_LIFETIME_THRESHOLD, MARKET_BUMP_AMOUNT);
+    }
+
+    fn extend_role<K: IntoVal<Env, Val>>(env: &Env, key: &K) {
+        env.storage().persistent().extend_ttl(key, ROLE_LIFETIME_THRESHOLD, ROLE_BUMP_AMOUNT);
+    }
+
+    fn role_key(role: Role, account: &Address) -> (Symbol, Role, Address) {
+        // Persistent rather than instance, so grants don't add to every call's footprint
+        (symbol_short!("ROLE"), role, account.clone())
     }
 
     fn market_key(market_id: u64) -> (Symbol, u64) {
         (symbol_short!("MKT"), market_id)
     }
 
+    fn market_metadata_key(market_id: u64) -> (Symbol, u64) {
+        (symbol_short!("MKTMETA"), market_id)
+    }
+
     fn stake_key(stake_id: u64) -> (Symbol, u64) {
         (symbol_short!("STK"), stake_id)
     }
 
-    fn market_stakes_key(market_id: u64) -> (Symbol, u64) {
-        (symbol_short!("MKTSTKS"), market_id)
+    fn market_stakes_key(market_id: u64, page: u32) -> (Symbol, u64, u32) {
+        (symbol_short!("MKTSTKS"), market_id, page)
+    }
+
+    /// Stake ids a user holds in one market, for `claim_all`. Settlement closes stakes without
+    /// touching this list, so it can name stakes that are gone.
+    fn user_stakes_key(user: &Address, market_id: u64) -> (Symbol, Address, u64) {
+        (symbol_short!("USRSTKS"), user.clone(), market_id)
+    }
+
+    /// Removes `id` from the list of stake ids stored under `key`.
+    fn remove_id<K: IntoVal<Env, Val>>(env: &Env, key: &K, id: u64) {
+        let ids: Vec<u64> = env.storage().persistent().get(key).unwrap_or(Vec::new(env));
+        let mut new_ids: Vec<u64> = Vec::new(env);
+        let mut i = 0u32;
+        while i < ids.len() {
+            let existing = ids.get(i).unwrap();
+            if existing != id {
+                new_ids.push_back(existing);
+            }
+            i += 1;
+        }
+        env.storage().persistent().set(key, &new_ids);
+        Self::extend_market(env, key);
+    }
+
+    /// Close time -> number of markets with exposure that close then. Markets stay counted until
+    /// settlement has paid their winners or they are voided.
+    fn closing_key() -> Symbol {
+        symbol_short!("CLOSING")
     }
 
     fn user_key(user: &Address) -> (Symbol, Address) {
@@ -430,16 +1420,225 @@ impl PredictionMarketContract {
         (symbol_short!("LP"), provider.clone())
     }
 
-    fn credit_user_balance(env: &Env, user: &Address, amount: i128) {
+    fn token_client<'a>(env: &'a Env, config: &Config) -> token::Client<'a> {
+        token::Client::new(env, &config.token)
+    }
+
+    /// The provider's position in the current share epoch; shares from an earlier epoch were
+    /// written off with the pool they were a claim on.
+    fn get_lp_account(env: &Env, config: &Config, provider: &Address) -> LpAccount {
+        let lp: Option<LpAccount> = env.storage().persistent().get(&Self::lp_key(provider));
+        match lp {
+            Some(lp) if lp.epoch == config.lp_epoch => lp,
+            _ => LpAccount { shares: 0, deposited: 0, epoch: config.lp_epoch },
+        }
+    }
+
+    fn lp_shares_value(shares: i128, total_shares: i128, pool: i128) -> Result<i128, Error> {
+        // value = shares * pool / total shares, never negative
+        if total_shares == 0 || pool <= 0 { return Ok(0); }
+        shares.checked_mul(pool).ok_or(Error::Overflow)?.checked_div(total_shares).ok_or(Error::Overflow)
+    }
+
+    /// Pool liquidity not reserved for the worst case of open markets.
+    fn free_liquidity(config: &Config) -> Result<i128, Error> {
+        config.total_liquidity.checked_sub(config.total_exposure).ok_or(Error::Overflow)
+    }
+
+    /// LPs can't enter or exit while a market with exposure is past its close time but not yet
+    /// settled, since by then its result may already be known. Only the earliest close time in CLOSING
+    /// matters, however many markets are open.
+    fn check_no_result_pending(env: &Env) -> Result<(), Error> {
+        let closing: Map<i64, u32> = env.storage().persistent().get(&Self::closing_key()).unwrap_or(Map::new(env));
+        let now = env.ledger().timestamp() as i64;
+        if closing.keys().first().is_some_and(|close_time| now >= close_time) { return Err(Error::LiquidityLocked); }
+        Ok(())
+    }
+
+    /// Saves a market whose exposure or close time may have changed, moving its count in CLOSING
+    /// to match.
+    fn save_market(env: &Env, market: &mut Market) {
+        let pending_close = if market.exposure > 0 { Some(market.close_time) } else { None };
+        if pending_close != market.pending_close {
+            let mut closing: Map<i64, u32> = env.storage().persistent().get(&Self::closing_key()).unwrap_or(Map::new(env));
+            if let Some(close_time) = market.pending_close {
+                let count = closing.get(close_time).unwrap_or(0);
+                if count > 1 { closing.set(close_time, count - 1); } else { closing.remove(close_time); }
+            }
+            if let Some(close_time) = pending_close {
+                closing.set(close_time, closing.get(close_time).unwrap_or(0) + 1);
+            }
+            env.storage().persistent().set(&Self::closing_key(), &closing);
+            Self::extend_market(env, &Self::closing_key());
+            market.pending_close = pending_close;
+        }
+        let market_key = Self::market_key(market.id);
+        env.storage().persistent().set(&market_key, market);
+        Self::extend_market(env, &market_key);
+    }
+
+    fn credit_user_balance(env: &Env, user: &Address, amount: i128) -> Result<(), Error> {
         let mut bal: i128 = env.storage().persistent().get(&Self::user_key(user)).unwrap_or(0i128);
-        bal = bal.checked_add(amount).expect("overflow credit user bal");
+        bal = bal.checked_add(amount).ok_or(Error::Overflow)?;
         env.storage().persistent().set(&Self::user_key(user), &bal);
+        Self::extend_balance(env, &Self::user_key(user));
+        Ok(())
+    }
+
+    fn current_prices(market: &Market) -> Result<Vec<i128>, Error> {
+        if market.reserves.iter().all(|reserve| reserve == 0) {
+            return Ok(market.odds.clone());
+        }
+        if market.pricing == PricingModel::Lmsr {
+            return lmsr::prices(&market.reserves, market.depth);
+        }
+        let total_reserve = Self::sum(&market.reserves)?;
+        let mut prices = Vec::new(market.reserves.env());
+        for reserve in market.reserves.iter() {
+            prices.push_back(Self::calculate_price_from_reserve(reserve, total_reserve)?);
+        }
+        Ok(prices)
+    }
+
+    /// Splits `total_reserve` across the outcomes in proportion to the market's odds. LMSR
+    /// markets ignore it and take the state whose prices are the odds at `b = depth`.
+    fn seed_reserves(market: &mut Market, total_reserve: i128) -> Result<(), Error> {
+        if market.pricing == PricingModel::Lmsr {
+            market.reserves = lmsr::seed(&market.odds, market.depth)?;
+            return Ok(());
+        }
+        let mut reserves = Vec::new(market.odds.env());
+        for odd in market.odds.iter() {
+            reserves.push_back(total_reserve.checked_mul(odd).ok_or(Error::Overflow)?.checked_div(TOTAL_PRICE_SUM).ok_or(Error::Overflow)?);
+        }
+        market.reserves = reserves;
+        Ok(())
+    }
+
+    /// Adds (or, for a negative `amount`, removes) `amount` of every outcome to the reserves
+    /// without moving prices, so complete-set legs can later be cashed out against them. LMSR
+    /// shifts each `q_i` by `amount`, which costs exactly `amount`; ReserveRatio splits it across
+    /// the reserves in proportion to the current prices.
+    fn shift_reserves(market: &mut Market, amount: i128) -> Result<(), Error> {
+        let total_reserve = Self::sum(&market.reserves)?;
+        for i in 0..market.reserves.len() {
+            let reserve = market.reserves.get(i).unwrap();
+            let delta = match market.pricing {
+                PricingModel::Lmsr => amount,
+                PricingModel::ReserveRatio => amount.checked_mul(reserve).ok_or(Error::Overflow)?.checked_div(total_reserve).ok_or(Error::Overflow)?,
+            };
+            let shifted = reserve.checked_add(delta).ok_or(Error::Overflow)?;
+            if market.pricing == PricingModel::ReserveRatio && shifted < 0 { return Err(Error::ReserveDepleted); }
+            market.reserves.set(i, shifted);
+        }
+        Ok(())
+    }
+
+    /// Checks and fills a stake against the config and market in memory, without touching storage,
+    /// so `quote_stake` fails exactly where `place_stake` would: (shares, avg_price, price_after).
+    fn apply_stake(env: &Env, config: &mut Config, market: &mut Market, outcome: u32, amount: i128) -> Result<(i128, i128, i128), Error> {
+        if market.status != MarketStatus::Active { return Err(Error::MarketNotActive); }
+        Self::check_betting_open(env, market)?;
+        if market.settlement == SettlementMode::Push && market.listed_stakes >= MAX_STAKERS_PER_MARKET { return Err(Error::StakerCapReached); }
+        let (shares, avg_price, price_after) = Self::compute_stake(market, outcome, amount)?;
+        let reserve_delta = match market.pricing {
+            PricingModel::ReserveRatio => amount,
+            PricingModel::Lmsr => shares,
+        };
+        Self::add_at(&mut market.reserves, outcome, reserve_delta)?;
+        Self::add_at(&mut market.shares, outcome, shares)?;
+        // The cost sits in the market's escrow until it settles, when it joins the pool
+        market.escrow = market.escrow.checked_add(amount).ok_or(Error::Overflow)?;
+        let exposure = Self::active_exposure(market)?;
+        Self::set_market_exposure(config, market, exposure)?;
+        if config.total_exposure > config.total_liquidity { return Err(Error::ExposureLimitExceeded); }
+        Ok((shares, avg_price, price_after))
+    }
+
+    /// Checks and closes a stake against the config and market in memory, without touching
+    /// storage, so `quote_cash_out` fails exactly where `cash_out` would: (gross, fee, net).
+    fn apply_cash_out(env: &Env, config: &mut Config, market: &mut Market, stake: &Stake) -> Result<(i128, i128, i128), Error> {
+        if market.status != MarketStatus::Active { return Err(Error::MarketNotActive); }
+        Self::check_betting_open(env, market)?;
+        let shares = stake.amount;
+        let (payout_before_fee, fee, payout_after_fee) = Self::compute_cash_out(market, stake, config.cashout_fee_percent)?;
+        let reserve_delta = match market.pricing {
+            PricingModel::ReserveRatio => payout_before_fee,
+            PricingModel::Lmsr => shares,
+        };
+        Self::add_at(&mut market.reserves, stake.outcome, reserve_delta.checked_neg().ok_or(Error::Overflow)?)?;
+        Self::add_at(&mut market.shares, stake.outcome, shares.checked_neg().ok_or(Error::Overflow)?)?;
+        // The closed stake's cost leaves escrow for the pool, which pays the exit price and keeps the fee
+        market.escrow = market.escrow.checked_sub(stake.cost).ok_or(Error::Overflow)?;
+        let pool_after = config.total_liquidity.checked_add(stake.cost).ok_or(Error::Overflow)?.checked_sub(payout_after_fee).ok_or(Error::Overflow)?;
+        if pool_after < 0 { return Err(Error::InsufficientLiquidity); }
+        config.total_liquidity = pool_after;
+        let exposure = Self::active_exposure(market)?;
+        Self::set_market_exposure(config, market, exposure)?;
+        if config.total_exposure > config.total_liquidity { return Err(Error::ExposureLimitExceeded); }
+        Ok((payout_before_fee, fee, payout_after_fee))
+    }
+
+    /// Pricing shared by `place_stake` and `quote_stake`: (shares, avg_price, price_after).
+    fn compute_stake(market: &Market, outcome: u32, amount: i128) -> Result<(i128, i128, i128), Error> {
+        let reserve = market.reserves.get(outcome).ok_or(Error::InvalidOutcome)?;
+        if market.pricing == PricingModel::Lmsr {
+            let mut q = market.reserves.clone();
+            let shares = lmsr::buy(&q, market.depth, outcome, amount)?;
+            if shares <= 0 { return Err(Error::AmountTooSmall); }
+            let avg_price = amount.checked_mul(DECIMALS).ok_or(Error::Overflow)?.checked_div(shares).ok_or(Error::Overflow)?;
+            Self::add_at(&mut q, outcome, shares)?;
+            let price_after = lmsr::prices(&q, market.depth)?.get(outcome).ok_or(Error::InvalidOutcome)?;
+            return Ok((shares, avg_price, price_after));
+        }
+        let total_reserve = Self::sum(&market.reserves)?;
+        let price_before = Self::calculate_price_from_reserve(reserve, total_reserve)?;
+        let price_after = Self::calculate_price_from_reserve(
+            reserve.checked_add(amount).ok_or(Error::Overflow)?,
+            total_reserve.checked_add(amount).ok_or(Error::Overflow)?
+        )?;
+        let avg_price = price_before.checked_add(price_after).ok_or(Error::Overflow)?.checked_div(2).ok_or(Error::Overflow)?;
+        let shares = amount.checked_mul(DECIMALS).ok_or(Error::Overflow)?.checked_div(avg_price).ok_or(Error::Overflow)?;
+        Ok((shares, avg_price, price_after))
+    }
+
+    /// Pricing shared by `cash_out` and `quote_cash_out`: (gross, fee, net).
+    fn compute_cash_out(market: &Market, stake: &Stake, fee_percent: i128) -> Result<(i128, i128, i128), Error> {
+        let payout_before_fee = match market.pricing {
+            PricingModel::ReserveRatio => Self::reserve_ratio_exit(market, stake)?,
+            PricingModel::Lmsr => lmsr::sell(&market.reserves, market.depth, stake.outcome, stake.amount)?,
+        };
+        // A ReserveRatio exit is paid out of the outcome's reserve, which must not go negative
+        if market.pricing == PricingModel::ReserveRatio && payout_before_fee > market.reserves.get(stake.outcome).ok_or(Error::InvalidOutcome)? {
+            return Err(Error::ReserveDepleted);
+        }
+        let fee = payout_before_fee.checked_mul(fee_percent).ok_or(Error::Overflow)?.checked_div(100).ok_or(Error::Overflow)?;
+        let payout_after_fee = payout_before_fee.checked_sub(fee).ok_or(Error::Overflow)?;
+        Ok((payout_before_fee, fee, payout_after_fee))
+    }
+
+    fn reserve_ratio_exit(market: &Market, stake: &Stake) -> Result<i128, Error> {
+        let reserve = market.reserves.get(stake.outcome).ok_or(Error::InvalidOutcome)?;
+        let shares = stake.amount;
+        let total_reserve = Self::sum(&market.reserves)?;
+        let price_before_exit = Self::calculate_price_from_reserve(reserve, total_reserve)?;
+        let estimated_payout = shares.checked_mul(price_before_exit).ok_or(Error::Overflow)?.checked_div(DECIMALS).ok_or(Error::Overflow)?;
+        let price_after_exit = if reserve <= estimated_payout {
+            0 // Would drain the reserve
+        } else {
+            Self::calculate_price_from_reserve(
+                reserve.checked_sub(estimated_payout).ok_or(Error::Overflow)?,
+                total_reserve.checked_sub(estimated_payout).ok_or(Error::Overflow)?
+            )?
+        };
+        let avg_exit_price = price_before_exit.checked_add(price_after_exit).ok_or(Error::Overflow)?.checked_div(2).ok_or(Error::Overflow)?;
+        shares.checked_mul(avg_exit_price).ok_or(Error::Overflow)?.checked_div(DECIMALS).ok_or(Error::Overflow)
     }
 
-    fn calculate_price_from_reserve(reserve: i128, total_reserve: i128) -> i128 {
+    fn calculate_price_from_reserve(reserve: i128, total_reserve: i128) -> Result<i128, Error> {
         // price = reserve * DECIMALS / total_reserve
-        if total_reserve == 0 { return 0; }
-        reserve.checked_mul(DECIMALS).expect("mul overflow").checked_div(total_reserve).expect("div error")
+        if total_reserve == 0 { return Ok(0); }
+        reserve.checked_mul(DECIMALS).ok_or(Error::Overflow)?.checked_div(total_reserve).ok_or(Error::Overflow)
     }
 }
 
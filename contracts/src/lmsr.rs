@@ -0,0 +1,126 @@
+//! Logarithmic Market Scoring Rule pricing in fixed-point i128 math.
+//!
+//! The market state `q` holds one entry per outcome and, like the liquidity parameter `b`, is in
+//! token units (6 decimals). Cost is `C(q) = b * ln(sum(exp(q_i / b)))` and the price of outcome
+//! `i` is `exp(q_i / b) / sum(exp(q_j / b))`, so prices always sum to 1 and the market maker's
+//! worst-case loss from uniform prices is `b * ln(n)`. Intermediate values use 18 decimals (WAD).
+
+use soroban_sdk::Vec;
+
+use crate::{Error, DECIMALS, TOTAL_PRICE_SUM};
+
+const WAD: i128 = 1_000_000_000_000_000_000;
+const LN2_WAD: i128 = 693_147_180_559_945_309;
+const MAX_EXP_WAD: i128 = 40 * WAD; // exp(40) * WAD still fits comfortably in an i128
+const MIN_EXP_WAD: i128 = -42 * WAD; // exp(-42) rounds to zero at 18 decimals
+
+/// e^x for an 18-decimal `x`.
+pub(crate) fn exp_wad(x: i128) -> Result<i128, Error> {
+    if x > MAX_EXP_WAD { return Err(Error::Overflow); }
+    if x < MIN_EXP_WAD { return Ok(0); }
+    // x = k * ln2 + r with |r| <= ln2 / 2, then e^x = 2^k * e^r
+    let half = if x >= 0 { LN2_WAD / 2 } else { -LN2_WAD / 2 };
+    let k = (x + half) / LN2_WAD;
+    let r = x - k * LN2_WAD;
+    let mut term = WAD;
+    let mut sum = WAD;
+    let mut n = 1;
+    while term != 0 {
+        term = term * r / WAD / n;
+        sum += term;
+        n += 1;
+    }
+    if k >= 0 {
+        sum.checked_mul(1i128 << k).ok_or(Error::Overflow)
+    } else {
+        Ok(sum >> -k)
+    }
+}
+
+/// ln(x) for an 18-decimal `x > 0`.
+pub(crate) fn ln_wad(x: i128) -> Result<i128, Error> {
+    if x <= 0 { return Err(Error::Overflow); }
+    // x = 2^k * m with m in [1, 2), then ln(m) = 2 * atanh((m - 1) / (m + 1))
+    let mut m = x;
+    let mut k: i128 = 0;
+    while m >= 2 * WAD {
+        m >>= 1;
+        k += 1;
+    }
+    while m < WAD {
+        m <<= 1;
+        k -= 1;
+    }
+    let z = (m - WAD) * WAD / (m + WAD);
+    let z2 = z * z / WAD;
+    let mut term = z;
+    let mut sum = 0;
+    let mut n = 1;
+    while term != 0 {
+        sum += term / n;
+        term = term * z2 / WAD;
+        n += 2;
+    }
+    Ok(k * LN2_WAD + 2 * sum)
+}
+
+/// exp((q_i - max q) / b) for each outcome and their sum; shifting by the max keeps every
+/// weight in (0, 1].
+fn weights(q: &Vec<i128>, b: i128) -> Result<(Vec<i128>, i128), Error> {
+    if b <= 0 { return Err(Error::InvalidDepth); }
+    let max = q.iter().max().ok_or(Error::InvalidOutcomeCount)?;
+    let mut w = Vec::new(q.env());
+    let mut sum: i128 = 0;
+    for qi in q.iter() {
+        let x = qi.checked_sub(max).ok_or(Error::Overflow)?.checked_mul(WAD).ok_or(Error::Overflow)? / b;
+        let wi = exp_wad(x)?;
+        sum = sum.checked_add(wi).ok_or(Error::Overflow)?;
+        w.push_back(wi);
+    }
+    Ok((w, sum))
+}
+
+/// Current price of each outcome (6 decimals).
+pub(crate) fn prices(q: &Vec<i128>, b: i128) -> Result<Vec<i128>, Error> {
+    let (w, sum) = weights(q, b)?;
+    let mut out = Vec::new(q.env());
+    for wi in w.iter() {
+        out.push_back(wi * DECIMALS / sum);
+    }
+    Ok(out)
+}
+
+/// Shares of `outcome` that `amount` buys: the `d` solving `C(q + d * e_k) - C(q) = amount`.
+pub(crate) fn buy(q: &Vec<i128>, b: i128, outcome: u32, amount: i128) -> Result<i128, Error> {
+    let (w, sum) = weights(q, b)?;
+    let wk = w.get(outcome).ok_or(Error::InvalidOutcome)?;
+    // exp(d / b) = (sum * exp(amount / b) - sum + w_k) / w_k. Taking the log as
+    // amount / b + ln(sum - (sum - w_k) * exp(-amount / b)) - ln(w_k) only ever needs exp of a
+    // negative exponent, so stakes far larger than `b` can't overflow it.
+    let x = amount.checked_mul(WAD).ok_or(Error::Overflow)? / b;
+    let shrink = exp_wad(x.checked_neg().ok_or(Error::Overflow)?)?;
+    let others = sum.checked_sub(wk).ok_or(Error::Overflow)?;
+    let remaining = sum.checked_sub(others.checked_mul(shrink).ok_or(Error::Overflow)? / WAD).ok_or(Error::Overflow)?;
+    let log_ratio = x.checked_add(ln_wad(remaining)?).ok_or(Error::Overflow)?.checked_sub(ln_wad(wk)?).ok_or(Error::Overflow)?;
+    Ok(b.checked_mul(log_ratio).ok_or(Error::Overflow)? / WAD)
+}
+
+/// Amount paid for selling `shares` of `outcome`: `C(q) - C(q - shares * e_k)`.
+pub(crate) fn sell(q: &Vec<i128>, b: i128, outcome: u32, shares: i128) -> Result<i128, Error> {
+    let (w, sum) = weights(q, b)?;
+    let wk = w.get(outcome).ok_or(Error::InvalidOutcome)?;
+    let shrink = exp_wad(-(shares.checked_mul(WAD).ok_or(Error::Overflow)? / b))?;
+    let remaining = sum - wk + wk * shrink / WAD;
+    let log_ratio = ln_wad(sum)?.checked_sub(ln_wad(remaining)?).ok_or(Error::Overflow)?;
+    Ok(b.checked_mul(log_ratio).ok_or(Error::Overflow)? / WAD)
+}
+
+/// Market state whose prices are the given odds, normalised to sum to 1.
+pub(crate) fn seed(odds: &Vec<i128>, b: i128) -> Result<Vec<i128>, Error> {
+    let mut q = Vec::new(odds.env());
+    for odd in odds.iter() {
+        let ln_price = ln_wad(odd.checked_mul(WAD).ok_or(Error::Overflow)? / TOTAL_PRICE_SUM)?;
+        q.push_back(b.checked_mul(ln_price).ok_or(Error::Overflow)? / WAD);
+    }
+    Ok(q)
+}
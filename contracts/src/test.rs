@@ -1,36 +1,154 @@
 #![cfg(test)]
+extern crate std;
 
-use soroban_sdk::{testutils::Address as _, Env, Address};
-use crate::{PredictionMarketContract, PredictionMarketContractClient};
+use soroban_sdk::{testutils::{storage::{Instance as _, Persistent as _}, Address as _, AuthorizedFunction, AuthorizedInvocation, Events, Ledger, MockAuth, MockAuthInvoke}, token, vec, Env, Address, Event, IntoVal, Map, String, Symbol, Val, Vec};
+use crate::{lmsr, AdminTransferCancelled, AdminTransferProposed, AdminTransferred, BALANCE_BUMP_AMOUNT, BettingWindowUpdated, ContractPaused, ContractUnpaused, MarketResumed, Role, RoleGranted, RoleRevoked, MarketSuspended, MAX_OUTCOMES, MAX_BUMP_BATCH, MIN_LOCKED_LP_SHARES, MAX_SETTLE_BATCH, MAX_STAKERS_PER_MARKET, STAKE_PAGE_SIZE, BALANCE_LIFETIME_THRESHOLD, DAY_IN_LEDGERS, INSTANCE_BUMP_AMOUNT, MARKET_BUMP_AMOUNT, ROLE_BUMP_AMOUNT, CashedOut, CashoutFeeUpdated, Deposited, Error, Initialized, LiquidityPoolReset, LiquidityProvided, LiquidityWithdrawn, LpPosition, MarketArchived, MarketCreated, MarketDepthUpdated, MarketMetadata, MarketMetadataUpdated, MarketSettled, MarketStatus, MarketVoided, OddsUpdated, PredictionMarketContract, PredictionMarketContractClient, PricingModel, SettlementMode, SettlementModeUpdated, SettlementStarted, StakePlaced, StakeRefunded, StakeSettled, Withdrawn, CompleteSetMinted, CompleteSetRedeemed};
+
+const MAX_WRITE_ENTRIES: u32 = 50; // ledger entries a mainnet transaction may write
 
 fn create_admin_and_client(env: &Env) -> (Address, PredictionMarketContractClient<'_>) {
-    let admin = Address::generate(&env);
-    let contract_id = env.register(PredictionMarketContract, ());
-    let client = PredictionMarketContractClient::new(&env, &contract_id);
-    client.initialize(&admin);
+    env.mock_all_auths();
+    let admin = Address::generate(env);
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let contract_id = env.register(PredictionMarketContract, (&admin, &token.address()));
+    let client = PredictionMarketContractClient::new(env, &contract_id);
     (admin, client)
 }
 
+fn mint(env: &Env, client: &PredictionMarketContractClient, to: &Address, amount: i128) {
+    token::StellarAssetClient::new(env, &client.get_token()).mint(to, &amount);
+}
+
+fn match_metadata(env: &Env, title: &str) -> MarketMetadata {
+    MarketMetadata {
+        title: String::from_str(env, title),
+        home_team: String::from_str(env, "Brazil"),
+        away_team: String::from_str(env, "England"),
+        competition: String::from_str(env, "FIFA World Cup"),
+        venue: String::from_str(env, "MetLife Stadium"),
+        fixture_id: String::from_str(env, "wc26-101"),
+        outcome_labels: labels(env, &["Brazil", "Draw", "England"]),
+        image_uri: String::from_str(env, ""),
+    }
+}
+
+fn labels(env: &Env, names: &[&str]) -> Vec<String> {
+    let mut out = Vec::new(env);
+    for name in names {
+        out.push_back(String::from_str(env, name));
+    }
+    out
+}
+
+fn three_way_odds(client: &PredictionMarketContractClient, market_id: u64) -> [i128; 3] {
+    let odds = client.get_current_odds(&market_id);
+    assert_eq!(odds.len(), 3);
+    [odds.get(0).unwrap(), odds.get(1).unwrap(), odds.get(2).unwrap()]
+}
+
+fn last_event(env: &Env) -> Vec<(Address, Vec<Val>, Val)> {
+    vec![env, env.events().all().last().unwrap()]
+}
+
+fn contract_event(env: &Env, client: &PredictionMarketContractClient, event: &impl Event) -> (Address, Vec<Val>, Val) {
+    (client.address.clone(), event.topics(env), event.data(env))
+}
+
+fn token_balance(env: &Env, client: &PredictionMarketContractClient, of: &Address) -> i128 {
+    token::Client::new(env, &client.get_token()).balance(of)
+}
+
 #[test]
 fn test_deposit_withdraw() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
+    mint(&env, &client, &user, 1_000_000);
     client.deposit(&user, &1_000_000); // $1.00
     assert_eq!(client.get_balance(&user), 1_000_000);
+    assert_eq!(token_balance(&env, &client, &user), 0);
+    assert_eq!(token_balance(&env, &client, &client.address), 1_000_000);
     client.withdraw(&user, &400_000); // withdraw $0.40
     assert_eq!(client.get_balance(&user), 600_000);
+    assert_eq!(token_balance(&env, &client, &user), 400_000);
+    assert_eq!(token_balance(&env, &client, &client.address), 600_000);
 }
 
 #[test]
 fn test_liquidity_provision() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
+    mint(&env, &client, &admin, 5_000_000);
     client.provide_liquidity(&admin, &5_000_000); // $5
     // Check LP balance through internal storage - no direct method exposed
     assert_eq!(client.total_liquidity(), 5_000_000);
+    assert_eq!(token_balance(&env, &client, &admin), 0);
+    assert_eq!(token_balance(&env, &client, &client.address), 5_000_000);
     client.withdraw_liquidity(&admin, &2_000_000);
     assert_eq!(client.total_liquidity(), 3_000_000);
+    assert_eq!(token_balance(&env, &client, &admin), 2_000_000);
+    assert_eq!(token_balance(&env, &client, &client.address), 3_000_000);
+}
+
+#[test]
+fn test_lp_shares_share_pool_losses_pro_rata() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let lp1 = Address::generate(&env);
+    let lp2 = Address::generate(&env);
+    let lp3 = Address::generate(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &lp1, 10_000_000);
+    client.provide_liquidity(&lp1, &10_000_000);
+    mint(&env, &client, &lp2, 10_000_000);
+    client.provide_liquidity(&lp2, &10_000_000);
+    // The first deposit into the pool leaves a few shares locked in it for good
+    assert_eq!(client.get_lp_position(&lp1), LpPosition { shares: 10_000_000 - MIN_LOCKED_LP_SHARES, value: 10_000_000 - MIN_LOCKED_LP_SHARES, pnl: -MIN_LOCKED_LP_SHARES });
+    assert_eq!(client.get_lp_position(&lp2), LpPosition { shares: 10_000_000, value: 10_000_000, pnl: 0 });
+
+    // The stake's cost joins the pool and the winnings are paid out of it
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+    client.settle_market(&admin, &1, &0);
+    let payout = client.get_balance(&user);
+    let pool = client.total_liquidity();
+    assert_eq!(pool, 20_000_000 + 1_000_000 - payout);
+
+    // Every share bears the same part of the loss
+    let pos1 = client.get_lp_position(&lp1);
+    let pos2 = client.get_lp_position(&lp2);
+    assert_eq!(pos1.value, pool * pos1.shares / 20_000_000);
+    assert_eq!(pos2.value, pool / 2);
+    assert_eq!(pos2.pnl, pool / 2 - 10_000_000);
+
+    // A late provider buys in at the reduced value per share
+    mint(&env, &client, &lp3, 10_000_000);
+    client.provide_liquidity(&lp3, &10_000_000);
+    let pos3 = client.get_lp_position(&lp3);
+    assert!(pos3.shares > 10_000_000);
+    assert!(pos3.value <= 10_000_000 && pos3.value >= 9_999_999);
+
+    // Exiting redeems the pro-rata value, not the original deposit
+    client.withdraw_liquidity(&lp1, &pos1.shares);
+    assert_eq!(token_balance(&env, &client, &lp1), pos1.value);
+    assert_eq!(client.get_lp_position(&lp1), LpPosition { shares: 0, value: 0, pnl: 0 });
+    assert_eq!(client.total_lp_shares(), pos2.shares + pos3.shares + MIN_LOCKED_LP_SHARES);
+}
+
+#[test]
+fn test_partial_lp_withdrawal_keeps_cost_basis_pro_rata() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let provider = Address::generate(&env);
+    mint(&env, &client, &admin, 1_000_000);
+    client.provide_liquidity(&admin, &1_000_000);
+    mint(&env, &client, &provider, 4_000_000);
+    client.provide_liquidity(&provider, &4_000_000);
+    client.withdraw_liquidity(&provider, &1_000_000);
+    assert_eq!(client.get_lp_position(&provider), LpPosition { shares: 3_000_000, value: 3_000_000, pnl: 0 });
+    assert_eq!(token_balance(&env, &client, &provider), 1_000_000);
 }
 
 #[test]
@@ -38,14 +156,16 @@ fn test_create_market_and_stake() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
     client.deposit(&user, &1_000_000);
     // Create market Brazil vs England with odds 40%, 25%, 34% => sums to 99
-    client.create_market(&admin, &soroban_sdk::symbol_short!("BrazilEng"), &1234567890, &400_000, &250_000, &340_000);
+    client.create_market(&admin, &match_metadata(&env, "BrazilEng"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
     let market = client.get_market(&1); // Get the created market by ID
     assert_eq!(market.id, 1);
-    assert_eq!(market.title, soroban_sdk::symbol_short!("BrazilEng"));
-    client.place_stake(&user, &1, &0, &500_000); // market_id=1, outcome=0 (home)
+    assert_eq!(client.get_market_metadata(&1), match_metadata(&env, "BrazilEng"));
+    client.place_stake(&user, &1, &0, &500_000, &0, &None); // market_id=1, outcome=0 (home)
     assert_eq!(client.get_balance(&user), 500_000);
 }
 
@@ -55,211 +175,215 @@ fn test_settlement() {
     let (admin, client) = create_admin_and_client(&env);
     let user1 = Address::generate(&env);
     let user2 = Address::generate(&env);
+    mint(&env, &client, &admin, 20_000_000);
     client.provide_liquidity(&admin, &20_000_000);
+    mint(&env, &client, &user1, 1_000_000);
     client.deposit(&user1, &1_000_000);
+    mint(&env, &client, &user2, 1_000_000);
     client.deposit(&user2, &1_000_000);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("BrazilEng"), &1234567890, &400_000, &250_000, &340_000);
-    client.place_stake(&user1, &1, &0, &1_000_000); // stake on home (outcome 0)
-    client.place_stake(&user2, &1, &1, &1_000_000); // stake on draw (outcome 1)
+    client.create_market(&admin, &match_metadata(&env, "BrazilEng"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user1, &1, &0, &1_000_000, &0, &None); // stake on home (outcome 0)
+    client.place_stake(&user2, &1, &1, &1_000_000, &0, &None); // stake on draw (outcome 1)
     client.settle_market(&admin, &1, &0); // settle market 1 with outcome 0 (home wins)
     // User1 should be paid (1m / 400k * 1m = 2.5m payout total, minus escrow logic)
     let bal1 = client.get_balance(&user1);
     let bal2 = client.get_balance(&user2);
     assert!(bal1 > 1_000_000); // user1 made profit
     assert_eq!(bal2, 0);       // user2 lost
+
+    // Winnings are paid out in real tokens funded by the liquidity pool
+    client.withdraw(&user1, &bal1);
+    assert_eq!(token_balance(&env, &client, &user1), bal1);
 }
 
 // SECURITY & EDGE CASE TESTS
 
 #[test]
-#[should_panic(expected = "unauthorized: admin only")]
 fn test_non_admin_create_market() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
     let attacker = Address::generate(&env);
-    client.create_market(&attacker, &soroban_sdk::symbol_short!("Attack"), &1234567890, &400_000, &250_000, &340_000);
+    assert_eq!(client.try_create_market(&attacker, &match_metadata(&env, "Attack"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio), Err(Ok(Error::Unauthorized)));
 }
 
 #[test]
-#[should_panic(expected = "unauthorized: admin only")]
 fn test_non_admin_settle_market() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     let attacker = Address::generate(&env);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
-    client.settle_market(&attacker, &1, &0);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_settle_market(&attacker, &1, &0), Err(Ok(Error::Unauthorized)));
 }
 
 #[test]
-#[should_panic(expected = "unauthorized: admin only")]
 fn test_non_admin_update_odds() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     let attacker = Address::generate(&env);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
-    client.update_odds(&attacker, &1, &300_000, &300_000, &390_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_update_odds(&attacker, &1, &vec![&env, 300_000, 300_000, 390_000]), Err(Ok(Error::Unauthorized)));
 }
 
 #[test]
-#[should_panic(expected = "unauthorized: admin only")]
 fn test_non_admin_archive_market() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     let attacker = Address::generate(&env);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
-    client.archive_market(&attacker, &1);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_archive_market(&attacker, &1), Err(Ok(Error::Unauthorized)));
 }
 
 #[test]
-#[should_panic(expected = "odds below minimum")]
 fn test_create_market_odds_too_low() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     // MIN_PRICE is 10,000 but we try with 5,000
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Invalid"), &1234567890, &5_000, &250_000, &735_000);
+    assert_eq!(client.try_create_market(&admin, &match_metadata(&env, "Invalid"), &1234567890, &vec![&env, 5_000, 250_000, 735_000], &990_000_000, &PricingModel::ReserveRatio), Err(Ok(Error::OddsBelowMinimum)));
 }
 
 #[test]
-#[should_panic(expected = "odds must sum to $0.99")]
 fn test_create_market_odds_wrong_sum() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     // Sum = 1_000_000 instead of 990_000
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Invalid"), &1234567890, &400_000, &300_000, &300_000);
+    assert_eq!(client.try_create_market(&admin, &match_metadata(&env, "Invalid"), &1234567890, &vec![&env, 400_000, 300_000, 300_000], &990_000_000, &PricingModel::ReserveRatio), Err(Ok(Error::InvalidOddsSum)));
 }
 
 #[test]
-#[should_panic(expected = "deposit positive")]
 fn test_deposit_zero_amount() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
-    client.deposit(&user, &0);
+    assert_eq!(client.try_deposit(&user, &0), Err(Ok(Error::InvalidAmount)));
 }
 
 #[test]
-#[should_panic(expected = "deposit positive")]
 fn test_deposit_negative_amount() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
-    client.deposit(&user, &-100_000);
+    assert_eq!(client.try_deposit(&user, &-100_000), Err(Ok(Error::InvalidAmount)));
 }
 
 #[test]
-#[should_panic(expected = "withdraw positive")]
 fn test_withdraw_zero_amount() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
+    mint(&env, &client, &user, 1_000_000);
     client.deposit(&user, &1_000_000);
-    client.withdraw(&user, &0);
+    assert_eq!(client.try_withdraw(&user, &0), Err(Ok(Error::InvalidAmount)));
 }
 
 #[test]
-#[should_panic(expected = "insufficient balance")]
 fn test_withdraw_more_than_balance() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
+    mint(&env, &client, &user, 500_000);
     client.deposit(&user, &500_000);
-    client.withdraw(&user, &600_000);
+    assert_eq!(client.try_withdraw(&user, &600_000), Err(Ok(Error::InsufficientBalance)));
 }
 
 #[test]
-#[should_panic(expected = "insufficient balance")]
 fn test_stake_more_than_balance() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 500_000);
     client.deposit(&user, &500_000);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
-    client.place_stake(&user, &1, &0, &600_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_place_stake(&user, &1, &0, &600_000, &0, &None), Err(Ok(Error::InsufficientBalance)));
 }
 
 #[test]
-#[should_panic(expected = "stake amount positive")]
 fn test_stake_zero_amount() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
     client.deposit(&user, &1_000_000);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
-    client.place_stake(&user, &1, &0, &0);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_place_stake(&user, &1, &0, &0, &0, &None), Err(Ok(Error::InvalidAmount)));
 }
 
 #[test]
-#[should_panic(expected = "invalid outcome")]
 fn test_stake_invalid_outcome() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
     client.deposit(&user, &1_000_000);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
-    client.place_stake(&user, &1, &3, &500_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_place_stake(&user, &1, &3, &500_000, &0, &None), Err(Ok(Error::InvalidOutcome)));
 }
 
 #[test]
-#[should_panic(expected = "market not found")]
 fn test_stake_nonexistent_market() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
+    mint(&env, &client, &user, 1_000_000);
     client.deposit(&user, &1_000_000);
-    client.place_stake(&user, &999, &0, &500_000);
+    assert_eq!(client.try_place_stake(&user, &999, &0, &500_000, &0, &None), Err(Ok(Error::MarketNotFound)));
 }
 
 #[test]
-#[should_panic(expected = "market not active")]
 fn test_stake_on_settled_market() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 2_000_000);
     client.deposit(&user, &2_000_000);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
-    client.place_stake(&user, &1, &0, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
     client.settle_market(&admin, &1, &0);
-    client.place_stake(&user, &1, &0, &500_000); // Should fail
+    assert_eq!(client.try_place_stake(&user, &1, &0, &500_000, &0, &None), Err(Ok(Error::MarketNotActive))); // Should fail
 }
 
 #[test]
-#[should_panic(expected = "market not active")]
 fn test_stake_on_archived_market() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
     client.deposit(&user, &1_000_000);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
     client.archive_market(&admin, &1);
-    client.place_stake(&user, &1, &0, &500_000);
+    assert_eq!(client.try_place_stake(&user, &1, &0, &500_000, &0, &None), Err(Ok(Error::MarketNotActive)));
 }
 
 #[test]
-#[should_panic(expected = "invalid outcome")]
 fn test_settle_invalid_outcome() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
-    client.settle_market(&admin, &1, &3);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_settle_market(&admin, &1, &3), Err(Ok(Error::InvalidOutcome)));
 }
 
 #[test]
-#[should_panic(expected = "market not active")]
 fn test_double_settlement() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
     client.deposit(&user, &1_000_000);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
-    client.place_stake(&user, &1, &0, &500_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &500_000, &0, &None);
     client.settle_market(&admin, &1, &0);
-    client.settle_market(&admin, &1, &0); // Should fail
+    assert_eq!(client.try_settle_market(&admin, &1, &0), Err(Ok(Error::MarketNotActive))); // Should fail
 }
 
 // Note: This test is removed because with CPMM dynamic pricing,
@@ -267,32 +391,31 @@ fn test_double_settlement() {
 // difficult to create insufficient liquidity scenarios naturally.
 
 #[test]
-#[should_panic(expected = "amount must be positive")]
 fn test_provide_liquidity_zero() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
     let provider = Address::generate(&env);
-    client.provide_liquidity(&provider, &0);
+    assert_eq!(client.try_provide_liquidity(&provider, &0), Err(Ok(Error::InvalidAmount)));
 }
 
 #[test]
-#[should_panic(expected = "amount must be positive")]
 fn test_withdraw_liquidity_zero() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
     let provider = Address::generate(&env);
+    mint(&env, &client, &provider, 1_000_000);
     client.provide_liquidity(&provider, &1_000_000);
-    client.withdraw_liquidity(&provider, &0);
+    assert_eq!(client.try_withdraw_liquidity(&provider, &0), Err(Ok(Error::InvalidAmount)));
 }
 
 #[test]
-#[should_panic(expected = "insufficient lp balance")]
 fn test_withdraw_more_liquidity_than_provided() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
     let provider = Address::generate(&env);
+    mint(&env, &client, &provider, 1_000_000);
     client.provide_liquidity(&provider, &1_000_000);
-    client.withdraw_liquidity(&provider, &2_000_000);
+    assert_eq!(client.try_withdraw_liquidity(&provider, &2_000_000), Err(Ok(Error::InsufficientLpBalance)));
 }
 
 #[test]
@@ -301,15 +424,18 @@ fn test_market_staker_count_tracking() {
     let (admin, client) = create_admin_and_client(&env);
     let user1 = Address::generate(&env);
     let user2 = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user1, 1_000_000);
     client.deposit(&user1, &1_000_000);
+    mint(&env, &client, &user2, 1_000_000);
     client.deposit(&user2, &1_000_000);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
 
     assert_eq!(client.get_staker_count(&1), 0);
-    client.place_stake(&user1, &1, &0, &500_000);
+    client.place_stake(&user1, &1, &0, &500_000, &0, &None);
     assert_eq!(client.get_staker_count(&1), 1);
-    client.place_stake(&user2, &1, &1, &500_000);
+    client.place_stake(&user2, &1, &1, &500_000, &0, &None);
     assert_eq!(client.get_staker_count(&1), 2);
 }
 
@@ -317,27 +443,32 @@ fn test_market_staker_count_tracking() {
 fn test_market_staker_count_increments() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
 
     // Test that staker count increments correctly
     for i in 0..10u32 {
         let user = Address::generate(&env);
+        mint(&env, &client, &user, 100_000);
         client.deposit(&user, &100_000);
-        client.place_stake(&user, &1, &(i % 3), &50_000);
+        client.place_stake(&user, &1, &(i % 3), &50_000, &0, &None);
         assert_eq!(client.get_staker_count(&1), i + 1);
     }
 }
 
 #[test]
-#[should_panic(expected = "already initialized")]
-fn test_double_initialization() {
+fn test_constructor_sets_admin_and_token() {
     let env = Env::default();
     let admin = Address::generate(&env);
-    let contract_id = env.register(PredictionMarketContract, ());
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    // No auth is mocked: the config is written at deployment, with no separate call to front-run
+    let contract_id = env.register(PredictionMarketContract, (&admin, &token.address()));
     let client = PredictionMarketContractClient::new(&env, &contract_id);
-    client.initialize(&admin);
-    client.initialize(&admin); // Should fail
+    assert_eq!(client.get_admin(), admin);
+    assert_eq!(client.get_token(), token.address());
+    assert_eq!(client.get_pending_admin(), None);
+    assert_eq!(client.total_liquidity(), 0);
 }
 
 #[test]
@@ -347,16 +478,19 @@ fn test_balance_consistency_after_multiple_operations() {
     let user1 = Address::generate(&env);
     let user2 = Address::generate(&env);
 
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user1, 1_000_000);
     client.deposit(&user1, &1_000_000);
+    mint(&env, &client, &user2, 2_000_000);
     client.deposit(&user2, &2_000_000);
 
     let initial_total_liq = client.total_liquidity();
     assert_eq!(initial_total_liq, 10_000_000); // Only LP provision counts, not user deposits
 
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
-    client.place_stake(&user1, &1, &0, &500_000);
-    client.place_stake(&user2, &1, &1, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user1, &1, &0, &500_000, &0, &None);
+    client.place_stake(&user2, &1, &1, &1_000_000, &0, &None);
 
     // Liquidity should remain unchanged (stakes don't add to LP pool)
     assert_eq!(client.total_liquidity(), 10_000_000); // Only LP provision
@@ -370,12 +504,14 @@ fn test_normal_settlement_works() {
     let (admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
 
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
     client.deposit(&user, &1_000_000);
 
     // Create market with valid odds
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
-    client.place_stake(&user, &1, &0, &500_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &500_000, &0, &None);
 
     // Normal settlement should work fine with valid prices
     client.settle_market(&admin, &1, &0);
@@ -385,7 +521,6 @@ fn test_normal_settlement_works() {
 }
 
 #[test]
-#[should_panic(expected = "overflow")]
 fn test_arithmetic_overflow_protection() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
@@ -395,10 +530,11 @@ fn test_arithmetic_overflow_protection() {
     let max_amount = i128::MAX;
 
     // First deposit should work
+    mint(&env, &client, &user, 1);
     client.deposit(&user, &1);
 
-    // Attempting to deposit MAX should cause overflow protection
-    client.deposit(&user, &max_amount); // Should panic due to overflow in credit_user_balance
+    // Attempting to deposit MAX should hit overflow protection
+    assert_eq!(client.try_deposit(&user, &max_amount), Err(Ok(Error::Overflow))); // Overflow in credit_user_balance
 }
 
 #[test]
@@ -407,15 +543,17 @@ fn test_payout_calculation_precision() {
     let (admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
 
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
     client.deposit(&user, &1_000_000);
 
     // Test with precise odds calculations
     // Odds: home=40%, draw=25%, away=34% (sum = 99%)
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
 
     let stake_amount = 1_000_000i128; // $1.00
-    client.place_stake(&user, &1, &0, &stake_amount);
+    client.place_stake(&user, &1, &0, &stake_amount, &0, &None);
 
     // With CPMM, the payout is determined by the reserve pool
     // Market gets 100M liquidity, reserve_home should be 40M initially
@@ -428,35 +566,31 @@ fn test_payout_calculation_precision() {
 }
 
 #[test]
-#[should_panic(expected = "market not found")]
 fn test_get_nonexistent_market() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
-    client.get_market(&999);
+    assert_eq!(client.try_get_market(&999), Err(Ok(Error::MarketNotFound)));
 }
 
 #[test]
-#[should_panic(expected = "market not found")]
 fn test_get_staker_count_nonexistent_market() {
     let env = Env::default();
     let (_admin, client) = create_admin_and_client(&env);
-    client.get_staker_count(&999);
+    assert_eq!(client.try_get_staker_count(&999), Err(Ok(Error::MarketNotFound)));
 }
 
 #[test]
-#[should_panic(expected = "market not found")]
 fn test_update_odds_nonexistent_market() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
-    client.update_odds(&admin, &999, &400_000, &250_000, &340_000);
+    assert_eq!(client.try_update_odds(&admin, &999, &vec![&env, 400_000, 250_000, 340_000]), Err(Ok(Error::MarketNotFound)));
 }
 
 #[test]
-#[should_panic(expected = "market not found")]
 fn test_settle_nonexistent_market() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
-    client.settle_market(&admin, &999, &0);
+    assert_eq!(client.try_settle_market(&admin, &999, &0), Err(Ok(Error::MarketNotFound)));
 }
 
 #[test]
@@ -466,33 +600,36 @@ fn test_dynamic_pricing_cpmm() {
     let user1 = Address::generate(&env);
     let user2 = Address::generate(&env);
 
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user1, 10_000_000);
     client.deposit(&user1, &10_000_000);
+    mint(&env, &client, &user2, 10_000_000);
     client.deposit(&user2, &10_000_000);
 
     // Create market with odds: home=40%, draw=25%, away=34%
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
 
     // Get initial odds (should be close to initial but calculated from reserves)
-    let (odds_home, odds_draw, odds_away) = client.get_current_odds(&1);
+    let [odds_home, odds_draw, odds_away] = three_way_odds(&client, 1);
     // Just verify they're in expected range and sum to roughly $0.99
     assert!(odds_home > 390_000 && odds_home < 410_000);
     assert!(odds_draw > 240_000 && odds_draw < 260_000);
     assert!(odds_away > 330_000 && odds_away < 350_000);
 
     // User1 stakes on home - should increase home price
-    client.place_stake(&user1, &1, &0, &1_000_000);
+    client.place_stake(&user1, &1, &0, &1_000_000, &0, &None);
 
     // Check that odds changed
-    let (new_odds_home, new_odds_draw, new_odds_away) = client.get_current_odds(&1);
+    let [new_odds_home, new_odds_draw, new_odds_away] = three_way_odds(&client, 1);
     assert!(new_odds_home > odds_home); // Home price increased
     assert!(new_odds_draw < odds_draw); // Draw price decreased relatively
     assert!(new_odds_away < odds_away); // Away price decreased relatively
 
     // User2 stakes on away - should increase away price
-    client.place_stake(&user2, &1, &2, &1_000_000);
+    client.place_stake(&user2, &1, &2, &1_000_000, &0, &None);
 
-    let (_final_odds_home, _final_odds_draw, final_odds_away) = client.get_current_odds(&1);
+    let [_final_odds_home, _final_odds_draw, final_odds_away] = three_way_odds(&client, 1);
     assert!(final_odds_away > new_odds_away); // Away price increased
 }
 
@@ -502,13 +639,15 @@ fn test_cash_out_functionality() {
     let (admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
 
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 10_000_000);
     client.deposit(&user, &10_000_000);
 
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
 
     // User places stake
-    client.place_stake(&user, &1, &0, &1_000_000);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
     let balance_after_stake = client.get_balance(&user);
     assert_eq!(balance_after_stake, 9_000_000);
 
@@ -520,7 +659,7 @@ fn test_cash_out_functionality() {
     // Cashout: min(1M, 1M * $21M / 5M) = min(1M, 4.2M) = 1M (capped)
     // After 5% fee: 950K
     // User gets back 95% of stake (loses 5% fee only)
-    client.cash_out(&user, &1);
+    client.cash_out(&user, &1, &0, &None);
 
     let balance_after_cashout = client.get_balance(&user);
     // User loses from: entry slippage + exit slippage + 5% fee
@@ -531,41 +670,43 @@ fn test_cash_out_functionality() {
 }
 
 #[test]
-#[should_panic(expected = "unauthorized: not stake owner")]
 fn test_cash_out_unauthorized() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     let user1 = Address::generate(&env);
     let user2 = Address::generate(&env);
 
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user1, 1_000_000);
     client.deposit(&user1, &1_000_000);
 
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
 
-    client.place_stake(&user1, &1, &0, &1_000_000);
+    client.place_stake(&user1, &1, &0, &1_000_000, &0, &None);
 
     // User2 tries to cash out User1's stake - should fail
-    client.cash_out(&user2, &1);
+    assert_eq!(client.try_cash_out(&user2, &1, &0, &None), Err(Ok(Error::NotStakeOwner)));
 }
 
 #[test]
-#[should_panic(expected = "stake not found")]
 fn test_cash_out_settled_market() {
     let env = Env::default();
     let (admin, client) = create_admin_and_client(&env);
     let user = Address::generate(&env);
 
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
     client.deposit(&user, &1_000_000);
 
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &250_000, &340_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
 
-    client.place_stake(&user, &1, &0, &1_000_000);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
     client.settle_market(&admin, &1, &0);
 
     // Try to cash out after settlement - stake is removed, should fail with "stake not found"
-    client.cash_out(&user, &1);
+    assert_eq!(client.try_cash_out(&user, &1, &0, &None), Err(Ok(Error::StakeNotFound)));
 }
 
 #[test]
@@ -575,11 +716,13 @@ fn test_arbitrage_exploit_repeated_cycles() {
     let attacker = Address::generate(&env);
 
     // Setup: Market with $1000 initial liquidity
+    mint(&env, &client, &admin, 10_000_000);
     client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &attacker, 1_000_000);
     client.deposit(&attacker, &1_000_000); // Give attacker $1000
 
     // Create market: home=$0.40, draw=$0.33, away=$0.26
-    client.create_market(&admin, &soroban_sdk::symbol_short!("Test"), &1234567890, &400_000, &330_000, &260_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 330_000, 260_000], &990_000_000, &PricingModel::ReserveRatio);
 
     let initial_balance = client.get_balance(&attacker);
     let mut current_balance = initial_balance;
@@ -593,11 +736,11 @@ fn test_arbitrage_exploit_repeated_cycles() {
         if stake_amount < 10_000 { break; }
 
         // Buy shares
-        client.place_stake(&attacker, &1, &0, &stake_amount);
+        client.place_stake(&attacker, &1, &0, &stake_amount, &0, &None);
 
         // Immediately cash out
         let stake_id = cycle + 1;
-        client.cash_out(&attacker, &stake_id);
+        client.cash_out(&attacker, &stake_id, &0, &None);
 
         current_balance = client.get_balance(&attacker);
 
@@ -605,9 +748,2225 @@ fn test_arbitrage_exploit_repeated_cycles() {
         assert!(current_balance <= balance_before + 100, "Arbitrage exploit detected in cycle!");
     }
 
-    let total_profit = current_balance as i128 - initial_balance as i128;
+    let total_profit = current_balance - initial_balance;
 
     // This should NOT be profitable - attacker should lose money or break even
     // Allow small profit due to rounding (< 0.1%)
     assert!(total_profit < initial_balance / 1000, "CRITICAL: Arbitrage exploit detected!");
 }
+
+// CONFIG TESTS
+
+#[test]
+fn test_config_tracks_counters_and_pool() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    let config = client.get_config();
+    assert_eq!(config.admin, admin);
+    assert_eq!(config.market_counter, 0);
+    assert_eq!(config.cashout_fee_percent, 5);
+    assert!(!config.paused);
+
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.create_market(&admin, &match_metadata(&env, "Test2"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &2, &1, &500_000, &0, &None);
+
+    let config = client.get_config();
+    assert_eq!(config.market_counter, 2);
+    assert_eq!(config.stake_counter, 1);
+    assert_eq!(config.total_liquidity, 10_000_000);
+    assert_eq!(config.total_lp_shares, 10_000_000);
+    // Config lives in instance storage only
+    env.as_contract(&client.address, || {
+        assert!(env.storage().instance().has(&soroban_sdk::symbol_short!("CONFIG")));
+        assert!(!env.storage().persistent().has(&soroban_sdk::symbol_short!("ADMIN")));
+    });
+}
+
+#[test]
+fn test_set_cashout_fee() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 2_000_000);
+    client.deposit(&user, &2_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+
+    assert_eq!(client.try_set_cashout_fee(&user, &0), Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.try_set_cashout_fee(&admin, &21), Err(Ok(Error::InvalidFee)));
+    assert_eq!(client.try_set_cashout_fee(&admin, &-1), Err(Ok(Error::InvalidFee)));
+
+    // The same stake on two identical markets, cashed out with 5% and then 0% fee
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+    client.cash_out(&user, &1, &0, &None);
+    let net_with_fee = client.get_balance(&user) - 1_000_000;
+    client.set_cashout_fee(&admin, &0);
+    assert_eq!(last_event(&env), vec![&env, contract_event(&env, &client, &CashoutFeeUpdated { by: admin.clone(), old: 5, new: 0 })]);
+    assert_eq!(client.get_config().cashout_fee_percent, 0);
+    client.create_market(&admin, &match_metadata(&env, "Test2"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    let balance_before = client.get_balance(&user);
+    client.place_stake(&user, &2, &0, &1_000_000, &0, &None);
+    client.cash_out(&user, &2, &0, &None);
+    let gross = client.get_balance(&user) - (balance_before - 1_000_000);
+    assert_eq!(net_with_fee, gross - gross * 5 / 100);
+}
+
+// AUTHORIZATION TESTS
+
+#[test]
+fn test_deposit_requires_user_auth() {
+    let env = Env::default();
+    let (_admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            user.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    client.address.clone(),
+                    Symbol::new(&env, "deposit"),
+                    (user.clone(), 1_000_000i128).into_val(&env),
+                )),
+                sub_invocations: std::vec![AuthorizedInvocation {
+                    function: AuthorizedFunction::Contract((
+                        client.get_token(),
+                        soroban_sdk::symbol_short!("transfer"),
+                        (user.clone(), client.address.clone(), 1_000_000i128).into_val(&env),
+                    )),
+                    sub_invocations: std::vec![],
+                }],
+            }
+        )]
+    );
+}
+
+#[test]
+fn test_deposit_unsigned_or_wrong_signer_rejected() {
+    let env = Env::default();
+    let (_admin, client) = create_admin_and_client(&env);
+    let victim = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    assert!(client.mock_auths(&[]).try_deposit(&victim, &1_000_000).is_err());
+    assert!(client
+        .mock_auths(&[MockAuth {
+            address: &attacker,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "deposit",
+                args: (&victim, 1_000_000i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_deposit(&victim, &1_000_000)
+        .is_err());
+    assert_eq!(client.get_balance(&victim), 0);
+}
+
+#[test]
+fn test_withdraw_wrong_signer_rejected() {
+    let env = Env::default();
+    let (_admin, client) = create_admin_and_client(&env);
+    let victim = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    mint(&env, &client, &victim, 1_000_000);
+    client.deposit(&victim, &1_000_000);
+    assert!(client.mock_auths(&[]).try_withdraw(&victim, &1_000_000).is_err());
+    assert!(client
+        .mock_auths(&[MockAuth {
+            address: &attacker,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "withdraw",
+                args: (&victim, 1_000_000i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_withdraw(&victim, &1_000_000)
+        .is_err());
+    assert_eq!(client.get_balance(&victim), 1_000_000);
+}
+
+#[test]
+fn test_place_stake_wrong_signer_rejected() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let victim = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &victim, 1_000_000);
+    client.deposit(&victim, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert!(client.mock_auths(&[]).try_place_stake(&victim, &1, &0, &500_000, &0, &None).is_err());
+    assert!(client
+        .mock_auths(&[MockAuth {
+            address: &attacker,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "place_stake",
+                args: (&victim, 1u64, 0u32, 500_000i128, 0i128, None::<u64>).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_place_stake(&victim, &1, &0, &500_000, &0, &None)
+        .is_err());
+    assert_eq!(client.get_balance(&victim), 1_000_000);
+}
+
+#[test]
+fn test_cash_out_wrong_signer_rejected() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let victim = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &victim, 1_000_000);
+    client.deposit(&victim, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&victim, &1, &0, &500_000, &0, &None);
+    assert!(client.mock_auths(&[]).try_cash_out(&victim, &1, &0, &None).is_err());
+    assert!(client
+        .mock_auths(&[MockAuth {
+            address: &attacker,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "cash_out",
+                args: (&victim, 1u64, 0i128, None::<u64>).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_cash_out(&victim, &1, &0, &None)
+        .is_err());
+    assert_eq!(client.get_market_stakes(&1).len(), 1);
+}
+
+#[test]
+fn test_liquidity_wrong_signer_rejected() {
+    let env = Env::default();
+    let (_admin, client) = create_admin_and_client(&env);
+    let provider = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    assert!(client.mock_auths(&[]).try_provide_liquidity(&provider, &1_000_000).is_err());
+    mint(&env, &client, &provider, 1_000_000);
+    client.mock_all_auths().provide_liquidity(&provider, &1_000_000);
+    assert!(client
+        .mock_auths(&[MockAuth {
+            address: &attacker,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "withdraw_liquidity",
+                args: (&provider, 1_000_000i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_withdraw_liquidity(&provider, &1_000_000)
+        .is_err());
+    assert_eq!(client.total_liquidity(), 1_000_000);
+}
+
+#[test]
+fn test_admin_functions_require_admin_signature() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let attacker = Address::generate(&env);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+
+    // Passing the real admin address without the admin's signature must fail
+    let metadata = match_metadata(&env, "Attack");
+    assert!(client
+        .mock_auths(&[MockAuth {
+            address: &attacker,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "create_market",
+                args: (&admin, metadata.clone(), 1234567890i64, vec![&env, 400_000i128, 250_000, 340_000], 990_000_000i128, PricingModel::ReserveRatio).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_create_market(&admin, &metadata, &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio)
+        .is_err());
+    assert!(client.mock_auths(&[]).try_update_odds(&admin, &1, &vec![&env, 300_000, 300_000, 390_000]).is_err());
+    assert!(client.mock_auths(&[]).try_settle_market(&admin, &1, &0).is_err());
+    assert!(client.mock_auths(&[]).try_archive_market(&admin, &1).is_err());
+
+    let market = client.get_market(&1);
+    assert_eq!(market.odds, vec![&env, 400_000, 250_000, 340_000]);
+    assert_eq!(market.status, MarketStatus::Active);
+    assert!(client.try_get_market(&2).is_err());
+}
+
+// EVENT TESTS
+
+#[test]
+fn test_initialize_event() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    let contract_id = env.register(PredictionMarketContract, (&admin, &token.address()));
+    let client = PredictionMarketContractClient::new(&env, &contract_id);
+    assert_eq!(
+        env.events().all(),
+        vec![&env, contract_event(&env, &client, &Initialized { admin, token: token.address() })]
+    );
+}
+
+#[test]
+fn test_market_admin_events() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let metadata = match_metadata(&env, "Test");
+    client.create_market(&admin, &metadata, &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(
+        env.events().all(),
+        vec![&env, contract_event(&env, &client, &MarketCreated {
+            market_id: 1,
+            metadata,
+            start_time: 1234567890,
+            odds: vec![&env, 400_000, 250_000, 340_000],
+            depth: 990_000_000,
+            pricing: PricingModel::ReserveRatio,
+        })]
+    );
+    client.update_odds(&admin, &1, &vec![&env, 300_000, 300_000, 390_000]);
+    assert_eq!(
+        env.events().all(),
+        vec![&env, contract_event(&env, &client, &OddsUpdated {
+            market_id: 1,
+            old_odds: vec![&env, 404_040, 252_525, 343_434],
+            new_odds: vec![&env, 303_030, 303_030, 393_939],
+        })]
+    );
+    client.archive_market(&admin, &1);
+    assert_eq!(
+        env.events().all(),
+        vec![
+            &env,
+            contract_event(&env, &client, &MarketVoided { market_id: 1, reason: soroban_sdk::symbol_short!("archived") }),
+            contract_event(&env, &client, &MarketArchived { market_id: 1 }),
+        ]
+    );
+}
+
+#[test]
+fn test_balance_and_liquidity_events() {
+    let env = Env::default();
+    let (_admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &user, 3_000_000);
+
+    client.deposit(&user, &1_000_000);
+    assert_eq!(last_event(&env), vec![&env, contract_event(&env, &client, &Deposited { user: user.clone(), amount: 1_000_000 })]);
+    client.withdraw(&user, &400_000);
+    assert_eq!(last_event(&env), vec![&env, contract_event(&env, &client, &Withdrawn { user: user.clone(), amount: 400_000 })]);
+    client.provide_liquidity(&user, &2_000_000);
+    assert_eq!(
+        last_event(&env),
+        vec![&env, contract_event(&env, &client, &LiquidityProvided { provider: user.clone(), amount: 2_000_000, shares: 2_000_000 - MIN_LOCKED_LP_SHARES })]
+    );
+    client.withdraw_liquidity(&user, &500_000);
+    assert_eq!(
+        last_event(&env),
+        vec![&env, contract_event(&env, &client, &LiquidityWithdrawn { provider: user.clone(), shares: 500_000, amount: 500_000 })]
+    );
+}
+
+#[test]
+fn test_stake_and_cash_out_events() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+    let events = env.events().all();
+    let stake = client.get_stake(&1);
+    assert_eq!(
+        events,
+        vec![&env, contract_event(&env, &client, &StakePlaced {
+            market_id: 1,
+            staker: user.clone(),
+            stake_id: 1,
+            outcome: 0,
+            amount: 1_000_000,
+            shares: stake.amount,
+            avg_price: stake.price,
+        })]
+    );
+
+    client.cash_out(&user, &1, &0, &None);
+    let events = env.events().all();
+    let net_payout = client.get_balance(&user);
+    let data: Map<Symbol, Val> = events.last().unwrap().2.into_val(&env);
+    let gross_payout: i128 = data.get(Symbol::new(&env, "gross_payout")).unwrap().into_val(&env);
+    let fee = gross_payout * 5 / 100; // 5% cash-out fee
+    assert_eq!(
+        events,
+        vec![&env, contract_event(&env, &client, &CashedOut {
+            market_id: 1,
+            staker: user.clone(),
+            stake_id: 1,
+            gross_payout,
+            fee,
+            net_payout,
+        })]
+    );
+}
+
+#[test]
+fn test_settlement_events_cover_every_stake() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let winner = Address::generate(&env);
+    let loser = Address::generate(&env);
+    mint(&env, &client, &admin, 20_000_000);
+    client.provide_liquidity(&admin, &20_000_000);
+    mint(&env, &client, &winner, 1_000_000);
+    client.deposit(&winner, &1_000_000);
+    mint(&env, &client, &loser, 1_000_000);
+    client.deposit(&loser, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&winner, &1, &0, &1_000_000, &0, &None);
+    client.place_stake(&loser, &1, &1, &1_000_000, &0, &None);
+    let winning_shares = client.get_stake(&1).amount;
+
+    client.settle_market(&admin, &1, &0);
+    assert_eq!(
+        env.events().all(),
+        vec![
+            &env,
+            contract_event(&env, &client, &SettlementStarted { market_id: 1, outcome: 0 }),
+            contract_event(&env, &client, &StakeSettled { market_id: 1, staker: winner.clone(), stake_id: 1, payout: winning_shares }),
+            contract_event(&env, &client, &StakeSettled { market_id: 1, staker: loser.clone(), stake_id: 2, payout: 0 }),
+            contract_event(&env, &client, &MarketSettled { market_id: 1, outcome: 0, total_payout: winning_shares }),
+        ]
+    );
+}
+
+// BATCHED SETTLEMENT TESTS
+
+#[test]
+fn test_settlement_of_full_market_in_batches() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    mint(&env, &client, &admin, 10_000_000_000);
+    client.provide_liquidity(&admin, &10_000_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    let mut winning_shares = 0;
+    for i in 0..MAX_STAKERS_PER_MARKET {
+        let user = Address::generate(&env);
+        mint(&env, &client, &user, 100_000);
+        client.deposit(&user, &100_000);
+        client.place_stake(&user, &1, &(i % 3), &100_000, &0, &None);
+        if i % 3 == 0 { winning_shares += client.get_stake(&(i as u64 + 1)).amount; }
+    }
+    // The market is full, and a quote says so rather than pricing a stake that would fail
+    assert_eq!(client.try_quote_stake(&1, &0, &100_000), Err(Ok(Error::StakerCapReached)));
+    let liquidity_before = client.total_liquidity();
+
+    // Every call, including the one that starts settlement, must fit in a single transaction's budget
+    env.cost_estimate().budget().reset_default();
+    client.settle_market(&admin, &1, &0);
+    assert!(env.cost_estimate().resources().write_entries <= MAX_WRITE_ENTRIES);
+    assert_eq!(client.get_market(&1).status, MarketStatus::Settling);
+    assert_eq!(client.get_market(&1).settle_cursor, MAX_SETTLE_BATCH);
+    let mut calls = 1;
+    loop {
+        env.cost_estimate().budget().reset_default();
+        let remaining = client.settle_batch(&1, &100);
+        assert!(env.cost_estimate().resources().write_entries <= MAX_WRITE_ENTRIES);
+        calls += 1;
+        if remaining == 0 { break; }
+    }
+    assert_eq!(calls, MAX_STAKERS_PER_MARKET.div_ceil(MAX_SETTLE_BATCH));
+
+    let market = client.get_market(&1);
+    assert_eq!(market.status, MarketStatus::Settled);
+    assert_eq!(market.total_payout, winning_shares);
+    assert_eq!(market.reserves, vec![&env, 0, 0, 0]);
+    assert_eq!(client.get_market_stakes(&1).len(), 0);
+    assert_eq!(client.total_liquidity(), liquidity_before + MAX_STAKERS_PER_MARKET as i128 * 100_000 - winning_shares);
+    assert_eq!(client.try_settle_batch(&1, &10), Err(Ok(Error::MarketNotSettling)));
+}
+
+#[test]
+fn test_settlement_blocks_trading_until_finished() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    mint(&env, &client, &admin, 100_000_000);
+    client.provide_liquidity(&admin, &100_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    let mut users = std::vec::Vec::new();
+    for _ in 0..(MAX_SETTLE_BATCH + 5) {
+        let user = Address::generate(&env);
+        mint(&env, &client, &user, 200_000);
+        client.deposit(&user, &200_000);
+        client.place_stake(&user, &1, &0, &100_000, &0, &None);
+        users.push(user);
+    }
+    client.settle_market(&admin, &1, &0);
+    let last = users.last().unwrap();
+    assert_eq!(client.try_place_stake(last, &1, &0, &100_000, &0, &None), Err(Ok(Error::MarketNotActive)));
+    assert_eq!(client.try_cash_out(last, &(MAX_SETTLE_BATCH as u64 + 5), &0, &None), Err(Ok(Error::MarketNotActive)));
+    assert_eq!(client.try_settle_market(&admin, &1, &1), Err(Ok(Error::MarketNotActive)));
+    assert_eq!(client.try_settle_batch(&1, &0), Err(Ok(Error::InvalidAmount)));
+
+    // Anyone can finish settlement, in batches as small as they like
+    assert_eq!(client.settle_batch(&1, &3), 2);
+    assert_eq!(client.settle_batch(&1, &3), 0);
+    assert_eq!(client.get_market(&1).status, MarketStatus::Settled);
+    assert!(client.get_balance(last) > 200_000);
+}
+
+#[test]
+fn test_settle_batch_errors() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    assert_eq!(client.try_settle_batch(&999, &10), Err(Ok(Error::MarketNotFound)));
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_settle_batch(&1, &10), Err(Ok(Error::MarketNotSettling)));
+}
+
+// CLAIM TESTS
+
+fn setup_resolvable_market(env: &Env) -> (Address, PredictionMarketContractClient<'_>, Address, Address) {
+    let (admin, client) = create_admin_and_client(env);
+    let winner = Address::generate(env);
+    let loser = Address::generate(env);
+    mint(env, &client, &admin, 20_000_000);
+    client.provide_liquidity(&admin, &20_000_000);
+    for user in [&winner, &loser] {
+        mint(env, &client, user, 2_000_000);
+        client.deposit(user, &2_000_000);
+    }
+    client.create_market(&admin, &match_metadata(env, "Test"), &1234567890, &vec![env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&winner, &1, &0, &500_000, &0, &None); // stake 1
+    client.place_stake(&loser, &1, &1, &500_000, &0, &None); // stake 2
+    client.place_stake(&winner, &1, &0, &500_000, &0, &None); // stake 3
+    client.place_stake(&winner, &1, &2, &500_000, &0, &None); // stake 4
+    (admin, client, winner, loser)
+}
+
+#[test]
+fn test_resolve_market_and_claim() {
+    let env = Env::default();
+    let (admin, client, winner, loser) = setup_resolvable_market(&env);
+    let winning_shares = client.get_stake(&1).amount + client.get_stake(&3).amount;
+    assert_eq!(client.get_market(&1).shares.get(0).unwrap(), winning_shares);
+    let liquidity_before = client.total_liquidity();
+
+    client.resolve_market(&admin, &1, &0);
+    let market = client.get_market(&1);
+    assert_eq!(market.status, MarketStatus::Settled);
+    assert_eq!(market.winning_outcome, Some(0));
+    assert_eq!(market.total_payout, winning_shares);
+    assert_eq!(client.total_liquidity(), liquidity_before + 2_000_000 - winning_shares); // set aside up front
+    assert_eq!(market.escrow, winning_shares);
+    assert_eq!(client.get_market_stakes(&1).len(), 4); // nothing paid out yet
+    assert_eq!(client.get_claimable(&winner, &1), winning_shares);
+    assert_eq!(client.get_claimable(&loser, &1), 0);
+
+    let stake_1 = client.get_stake(&1).amount;
+    assert_eq!(client.claim(&winner, &1), stake_1);
+    assert_eq!(client.get_balance(&winner), 500_000 + stake_1);
+    assert_eq!(client.get_claimable(&winner, &1), winning_shares - stake_1);
+    assert_eq!(client.get_market_stakes(&1).len(), 3);
+    assert_eq!(client.total_liquidity(), liquidity_before + 2_000_000 - winning_shares); // claims don't touch the pool
+    assert_eq!(client.get_market(&1).escrow, winning_shares - stake_1);
+}
+
+#[test]
+fn test_double_claim_rejected() {
+    let env = Env::default();
+    let (admin, client, winner, _loser) = setup_resolvable_market(&env);
+    client.resolve_market(&admin, &1, &0);
+    client.claim(&winner, &1);
+    let balance = client.get_balance(&winner);
+    assert_eq!(client.try_claim(&winner, &1), Err(Ok(Error::StakeNotFound)));
+    assert_eq!(client.get_balance(&winner), balance);
+}
+
+#[test]
+fn test_claim_losing_stake_rejected() {
+    let env = Env::default();
+    let (admin, client, winner, loser) = setup_resolvable_market(&env);
+    client.resolve_market(&admin, &1, &0);
+    assert_eq!(client.try_claim(&loser, &2), Err(Ok(Error::NotWinningStake)));
+    assert_eq!(client.try_claim(&winner, &4), Err(Ok(Error::NotWinningStake)));
+    assert_eq!(client.try_claim(&loser, &1), Err(Ok(Error::NotStakeOwner)));
+    assert_eq!(client.get_balance(&loser), 1_500_000);
+}
+
+#[test]
+fn test_claim_before_resolution_rejected() {
+    let env = Env::default();
+    let (admin, client, winner, _loser) = setup_resolvable_market(&env);
+    assert_eq!(client.try_claim(&winner, &1), Err(Ok(Error::MarketNotSettled)));
+    assert_eq!(client.try_claim_all(&winner, &1), Err(Ok(Error::MarketNotSettled)));
+    assert_eq!(client.try_resolve_market(&admin, &1, &3), Err(Ok(Error::InvalidOutcome)));
+    client.resolve_market(&admin, &1, &0);
+    assert_eq!(client.try_resolve_market(&admin, &1, &0), Err(Ok(Error::MarketNotActive)));
+    assert_eq!(client.try_settle_market(&admin, &1, &0), Err(Ok(Error::MarketNotActive)));
+}
+
+#[test]
+fn test_claim_rejected_during_push_settlement() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    mint(&env, &client, &admin, 100_000_000);
+    client.provide_liquidity(&admin, &100_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    let mut users = std::vec::Vec::new();
+    for _ in 0..(MAX_SETTLE_BATCH + 5) {
+        let user = Address::generate(&env);
+        mint(&env, &client, &user, 100_000);
+        client.deposit(&user, &100_000);
+        client.place_stake(&user, &1, &0, &100_000, &0, &None);
+        users.push(user);
+    }
+    client.settle_market(&admin, &1, &0);
+    assert_eq!(client.get_market(&1).status, MarketStatus::Settling);
+
+    // The last stakes are still waiting for settle_batch and must not be claimable
+    let last = users.last().unwrap();
+    let last_stake = MAX_SETTLE_BATCH as u64 + 5;
+    assert_eq!(client.get_claimable(last, &1), 0);
+    assert_eq!(client.try_claim(last, &last_stake), Err(Ok(Error::MarketNotSettled)));
+    assert_eq!(client.try_claim_all(last, &1), Err(Ok(Error::MarketNotSettled)));
+    assert_eq!(client.get_balance(last), 0);
+
+    let winnings = client.get_stake(&last_stake).amount;
+    assert_eq!(client.settle_batch(&1, &10), 0);
+    let market = client.get_market(&1);
+    assert_eq!(market.status, MarketStatus::Settled);
+    assert_eq!(market.escrow, 0);
+    assert_eq!(client.get_balance(last), winnings);
+    assert_eq!(client.try_claim_all(last, &1), Err(Ok(Error::MarketNotSettled)));
+    assert_eq!(client.get_balance(last), winnings);
+}
+
+#[test]
+fn test_claim_all() {
+    let env = Env::default();
+    let (admin, client, winner, loser) = setup_resolvable_market(&env);
+    let winning_shares = client.get_stake(&1).amount + client.get_stake(&3).amount;
+    client.resolve_market(&admin, &1, &0);
+
+    assert_eq!(client.claim_all(&winner, &1), winning_shares);
+    assert_eq!(client.get_balance(&winner), 500_000 + winning_shares);
+    assert_eq!(client.claim_all(&winner, &1), 0);
+    assert_eq!(client.claim_all(&loser, &1), 0);
+    assert_eq!(client.get_market_stakes(&1).len(), 0); // losing stakes are closed too
+    assert_eq!(client.try_get_stake(&4), Err(Ok(Error::StakeNotFound)));
+}
+
+#[test]
+fn test_pull_market_has_no_staker_cap() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000_000);
+    client.provide_liquidity(&admin, &10_000_000_000);
+    mint(&env, &client, &user, 200_000_000);
+    client.deposit(&user, &200_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.set_settlement_mode(&admin, &1, &SettlementMode::Pull);
+    assert_eq!(last_event(&env), vec![&env, contract_event(&env, &client, &SettlementModeUpdated { market_id: 1, mode: SettlementMode::Pull })]);
+
+    let stakes = MAX_STAKERS_PER_MARKET + 10;
+    for i in 0..stakes {
+        client.place_stake(&user, &1, &(i % 3), &100_000, &0, &None);
+    }
+    assert_eq!(client.get_staker_count(&1), stakes);
+
+    // Too many stakes to go back to push settlement, which also isn't available to pull markets
+    assert_eq!(client.try_set_settlement_mode(&admin, &1, &SettlementMode::Push), Err(Ok(Error::StakerCapReached)));
+    assert_eq!(client.try_settle_market(&admin, &1, &0), Err(Ok(Error::SettlementModeMismatch)));
+    client.resolve_market(&admin, &1, &0);
+    let last_stake = stakes as u64 - 1; // outcome 0, since stake i backs outcome (i - 1) % 3
+    let winnings = client.get_stake(&last_stake).amount;
+    assert_eq!(client.claim(&user, &last_stake), winnings);
+}
+
+// VOID TESTS
+
+#[test]
+fn test_cancelled_match_refunds_every_staker() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    mint(&env, &client, &admin, 100_000_000);
+    client.provide_liquidity(&admin, &100_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    let mut users = std::vec::Vec::new();
+    for i in 0..(MAX_SETTLE_BATCH * 2 + 3) {
+        let user = Address::generate(&env);
+        mint(&env, &client, &user, 1_000_000);
+        client.deposit(&user, &1_000_000);
+        client.place_stake(&user, &1, &(i % 3), &(100_000 + i as i128 * 1_000), &0, &None);
+        users.push(user);
+    }
+    client.cash_out(&users[0], &1, &0, &None); // closed positions are not refunded twice
+    let cashed_out_balance = client.get_balance(&users[0]);
+    let liquidity_before = client.total_liquidity();
+
+    client.void_market(&admin, &1, &soroban_sdk::symbol_short!("cancelled"));
+    let market = client.get_market(&1);
+    assert_eq!(market.status, MarketStatus::Voiding);
+    assert_eq!(market.void_reason, Some(soroban_sdk::symbol_short!("cancelled")));
+    assert_eq!(client.try_place_stake(&users[1], &1, &0, &100_000, &0, &None), Err(Ok(Error::MarketNotActive)));
+    // Every stake in a batch is refunded to a different user, the most writes a batch can need
+    assert_eq!(client.settle_batch(&1, &MAX_SETTLE_BATCH), 3); // the cashed-out stake stays listed
+    assert!(env.cost_estimate().resources().write_entries <= MAX_WRITE_ENTRIES);
+    assert_eq!(client.settle_batch(&1, &MAX_SETTLE_BATCH), 0);
+
+    let market = client.get_market(&1);
+    assert_eq!(market.status, MarketStatus::Archived);
+    assert_eq!(client.get_market_stakes(&1).len(), 0);
+    assert_eq!(client.get_balance(&users[0]), cashed_out_balance);
+    for user in users.iter().skip(1) {
+        assert_eq!(client.get_balance(user), 1_000_000);
+    }
+    assert_eq!(client.total_liquidity(), liquidity_before);
+    assert_eq!(client.try_settle_batch(&1, &10), Err(Ok(Error::MarketNotSettling)));
+}
+
+#[test]
+fn test_archive_active_market_refunds_stakes() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &600_000, &0, &None);
+    assert_eq!(client.get_stake(&1).cost, 600_000);
+
+    client.archive_market(&admin, &1);
+    assert_eq!(
+        env.events().all(),
+        vec![
+            &env,
+            contract_event(&env, &client, &MarketVoided { market_id: 1, reason: soroban_sdk::symbol_short!("archived") }),
+            contract_event(&env, &client, &StakeRefunded { market_id: 1, staker: user.clone(), stake_id: 1, refund: 600_000 }),
+            contract_event(&env, &client, &MarketArchived { market_id: 1 }),
+        ]
+    );
+    let market = client.get_market(&1);
+    assert_eq!(market.status, MarketStatus::Archived);
+    assert_eq!(market.total_payout, 600_000);
+    assert_eq!(client.get_balance(&user), 1_000_000);
+    assert_eq!(client.try_get_stake(&1), Err(Ok(Error::StakeNotFound)));
+    assert_eq!(client.try_archive_market(&admin, &1), Err(Ok(Error::MarketNotActive)));
+}
+
+#[test]
+fn test_void_market_errors() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let attacker = Address::generate(&env);
+    let reason = soroban_sdk::symbol_short!("cancelled");
+    assert_eq!(client.try_void_market(&admin, &1, &reason), Err(Ok(Error::MarketNotFound)));
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_void_market(&attacker, &1, &reason), Err(Ok(Error::Unauthorized)));
+    client.resolve_market(&admin, &1, &0);
+    assert_eq!(client.try_void_market(&admin, &1, &reason), Err(Ok(Error::MarketNotActive)));
+}
+
+// EXPOSURE TESTS
+
+#[test]
+fn test_exposure_tracks_worst_case_outcome() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 20_000_000);
+    client.provide_liquidity(&admin, &20_000_000);
+    mint(&env, &client, &user, 3_000_000);
+    client.deposit(&user, &3_000_000);
+    client.create_market(&admin, &match_metadata(&env, "A"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.create_market(&admin, &match_metadata(&env, "B"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+    client.place_stake(&user, &1, &1, &500_000, &0, &None);
+    let home_shares = client.get_stake(&1).amount;
+    let draw_shares = client.get_stake(&2).amount;
+    // Worst case is the largest payout on one outcome, less the escrowed costs that join the pool
+    assert_eq!(client.get_market(&1).escrow, 1_500_000);
+    assert_eq!(client.get_market_exposure(&1), home_shares.max(draw_shares) - 1_500_000);
+    client.place_stake(&user, &2, &2, &1_000_000, &0, &None);
+    let away_shares = client.get_stake(&3).amount;
+    assert_eq!(client.get_market_exposure(&2), away_shares - 1_000_000);
+    assert_eq!(client.get_total_exposure(), home_shares.max(draw_shares) - 1_500_000 + away_shares - 1_000_000);
+
+    // Exposure is released as positions close or markets finish
+    client.cash_out(&user, &3, &0, &None);
+    assert_eq!(client.get_market_exposure(&2), 0);
+    client.void_market(&admin, &2, &soroban_sdk::symbol_short!("cancelled"));
+    client.settle_market(&admin, &1, &1);
+    assert_eq!(client.get_market_exposure(&1), 0);
+    assert_eq!(client.get_total_exposure(), 0);
+}
+
+#[test]
+fn test_stake_rejected_when_exposure_exceeds_pool() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 1_000_000);
+    client.provide_liquidity(&admin, &1_000_000);
+    mint(&env, &client, &user, 2_000_000);
+    client.deposit(&user, &2_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+
+    client.place_stake(&user, &1, &0, &500_000, &0, &None); // ~1.25M shares against 0.5M escrow, within the 1M pool
+    assert_eq!(client.try_place_stake(&user, &1, &0, &500_000, &0, &None), Err(Ok(Error::ExposureLimitExceeded)));
+    assert_eq!(client.try_quote_stake(&1, &0, &500_000), Err(Ok(Error::ExposureLimitExceeded)));
+    assert_eq!(client.get_balance(&user), 1_500_000);
+    client.place_stake(&user, &1, &1, &250_000, &0, &None); // hedging another outcome doesn't raise the worst case
+    assert!(client.get_total_exposure() <= client.total_liquidity());
+}
+
+#[test]
+fn test_cash_out_rejected_when_exposure_exceeds_pool() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 1_000_000);
+    client.provide_liquidity(&admin, &1_000_000);
+    mint(&env, &client, &user, 5_000_000);
+    client.deposit(&user, &5_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &1, &200_000, &0, &None); // stake 1, on the draw
+
+    // Fill the pool's cover with home stakes until the next one is refused
+    while client.try_place_stake(&user, &1, &0, &20_000, &0, &None).is_ok() {}
+    assert!(client.get_total_exposure() <= client.total_liquidity());
+
+    // Closing the draw stake takes its cost out of escrow, which would leave home winners underfunded
+    assert_eq!(client.try_cash_out(&user, &1, &0, &None), Err(Ok(Error::ExposureLimitExceeded)));
+    assert_eq!(client.try_quote_cash_out(&1), Err(Ok(Error::ExposureLimitExceeded)));
+    assert!(client.get_total_exposure() <= client.total_liquidity());
+    client.settle_market(&admin, &1, &0);
+    while client.settle_batch(&1, &MAX_SETTLE_BATCH) > 0 {}
+    assert_eq!(client.get_market(&1).status, MarketStatus::Settled);
+}
+
+#[test]
+fn test_lp_withdrawal_limited_to_free_liquidity() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 5_000_000);
+    client.provide_liquidity(&admin, &5_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+    let exposure = client.get_total_exposure();
+
+    // Shares redeem only the liquidity not reserved for the open market
+    let shares = 5_000_000 - MIN_LOCKED_LP_SHARES;
+    let value = (5_000_000 - exposure) * shares / 5_000_000;
+    assert_eq!(client.get_lp_position(&admin).value, value);
+    client.withdraw_liquidity(&admin, &shares);
+    assert_eq!(token_balance(&env, &client, &admin), value);
+    assert_eq!(client.total_liquidity(), 5_000_000 - value);
+
+    // The remaining pool always covers the winners
+    client.settle_market(&admin, &1, &0);
+    assert_eq!(client.get_balance(&user), exposure + 1_000_000);
+    assert_eq!(client.total_liquidity(), 5_000_000 - value - exposure);
+}
+
+#[test]
+fn test_lp_exit_cannot_dodge_open_market_losses() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let lp1 = Address::generate(&env);
+    let lp2 = Address::generate(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 1_000_000);
+    client.provide_liquidity(&admin, &1_000_000);
+    mint(&env, &client, &lp1, 10_000_000);
+    client.provide_liquidity(&lp1, &10_000_000);
+    mint(&env, &client, &lp2, 10_000_000);
+    client.provide_liquidity(&lp2, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.create_market(&admin, &match_metadata(&env, "Empty"), &1234567000, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+    let exposure = client.get_total_exposure();
+
+    // An early exit leaves the exiting LP's part of the reserve behind
+    env.ledger().set_timestamp(1234567000); // market 2 has closed, but holds no stakes
+    client.withdraw_liquidity(&lp1, &10_000_000);
+    let lp1_pnl = token_balance(&env, &client, &lp1) - 10_000_000;
+    assert!((lp1_pnl + exposure * 10 / 21).abs() <= 1);
+
+    // Once the match starts the result may be known, so exits wait for settlement
+    env.ledger().set_timestamp(1234567890);
+    assert_eq!(client.try_withdraw_liquidity(&lp2, &1_000_000), Err(Ok(Error::LiquidityLocked)));
+    client.settle_market(&admin, &1, &0);
+
+    // The home win costs the pool the full exposure, shared pro rata by every provider
+    let pos2 = client.get_lp_position(&lp2);
+    assert!((pos2.pnl - lp1_pnl).abs() <= 1);
+    client.withdraw_liquidity(&lp2, &pos2.shares);
+    assert_eq!(token_balance(&env, &client, &lp2), pos2.value);
+}
+
+#[test]
+fn test_lp_entry_cannot_buy_into_known_results() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let late_lp = Address::generate(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+
+    // After kickoff the losing stake's escrow is as good as the pool's, so nobody can buy in for a share of it
+    env.ledger().set_timestamp(1234567890);
+    mint(&env, &client, &late_lp, 10_000_000);
+    assert_eq!(client.try_provide_liquidity(&late_lp, &10_000_000), Err(Ok(Error::LiquidityLocked)));
+    client.settle_market(&admin, &1, &1);
+    assert_eq!(client.total_liquidity(), 11_000_000);
+
+    // Once settled the escrow is in the pool and new providers buy in at its full value
+    let admin_value = client.get_lp_position(&admin).value;
+    client.provide_liquidity(&late_lp, &10_000_000);
+    assert!(client.get_lp_position(&late_lp).value <= 10_000_000);
+    assert!(client.get_lp_position(&admin).value >= admin_value);
+}
+
+#[test]
+fn test_liquidity_lock_follows_close_time_and_exposure() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &500_000, &0, &None);
+
+    // Moving the close time earlier moves the lock with it
+    env.ledger().set_timestamp(1234567000);
+    client.withdraw_liquidity(&admin, &1_000);
+    client.set_betting_window(&admin, &1, &1234567000, &None);
+    assert_eq!(client.try_withdraw_liquidity(&admin, &1_000), Err(Ok(Error::LiquidityLocked)));
+    client.set_betting_window(&admin, &1, &1234567500, &None);
+    client.withdraw_liquidity(&admin, &1_000);
+
+    // A market whose stakes have all closed no longer holds the pool
+    client.cash_out(&user, &1, &0, &None);
+    assert_eq!(client.get_market_exposure(&1), 0);
+    env.ledger().set_timestamp(1234567890);
+    client.withdraw_liquidity(&admin, &1_000);
+}
+
+#[test]
+fn test_last_lp_exit_leaves_reserve_to_locked_shares() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let lp = Address::generate(&env);
+    let newcomer = Address::generate(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &lp, 5_000_000);
+    client.provide_liquidity(&lp, &5_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+
+    // The only provider leaves, but the locked shares keep the reserve from going ownerless
+    client.withdraw_liquidity(&lp, &client.get_lp_position(&lp).shares);
+    assert_eq!(client.total_lp_shares(), MIN_LOCKED_LP_SHARES);
+    client.settle_market(&admin, &1, &1);
+    let pool = client.total_liquidity();
+    assert!(pool > 1_000_000);
+
+    // A newcomer buys in at the locked shares' value, so can't take the reserve
+    mint(&env, &client, &newcomer, pool);
+    assert_eq!(client.try_provide_liquidity(&newcomer, &1), Err(Ok(Error::AmountTooSmall)));
+    client.provide_liquidity(&newcomer, &pool);
+    client.withdraw_liquidity(&newcomer, &client.get_lp_position(&newcomer).shares);
+    assert!(token_balance(&env, &client, &newcomer) <= pool);
+}
+
+#[test]
+fn test_provide_liquidity_after_pool_drained() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let lp1 = Address::generate(&env);
+    let lp2 = Address::generate(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+
+    // Fund exactly the stake's worst case, so a win pays out the whole pool. A quote would
+    // refuse the stake while the pool is empty, so price it directly
+    let (shares, _, _) = PredictionMarketContract::compute_stake(&client.get_market(&1), 0, 1_000_000).unwrap();
+    let cover = shares - 1_000_000;
+    mint(&env, &client, &lp1, cover);
+    client.provide_liquidity(&lp1, &cover);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+    client.settle_market(&admin, &1, &0);
+    assert_eq!(client.total_liquidity(), 0);
+    assert_eq!(client.total_lp_shares(), cover);
+
+    // The worthless shares are written off and the pool restarts at 1:1
+    mint(&env, &client, &lp2, 5_000_000);
+    client.provide_liquidity(&lp2, &5_000_000);
+    assert_eq!(
+        vec![&env, env.events().all().get(0).unwrap()],
+        vec![&env, contract_event(&env, &client, &LiquidityPoolReset { epoch: 1, written_off_shares: cover })]
+    );
+    assert_eq!(client.total_lp_shares(), 5_000_000);
+    assert_eq!(client.get_lp_position(&lp2), LpPosition { shares: 5_000_000 - MIN_LOCKED_LP_SHARES, value: 5_000_000 - MIN_LOCKED_LP_SHARES, pnl: -MIN_LOCKED_LP_SHARES });
+    assert_eq!(client.get_lp_position(&lp1), LpPosition { shares: 0, value: 0, pnl: 0 });
+    assert_eq!(client.try_withdraw_liquidity(&lp1, &cover), Err(Ok(Error::InsufficientLpBalance)));
+
+    // The refilled pool can cover new stakes again
+    client.create_market(&admin, &match_metadata(&env, "Next"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &2, &1, &500_000, &0, &None);
+    assert!(client.get_total_exposure() > 0);
+}
+
+// MONEY FLOW TESTS
+
+fn assert_collateral_invariant(env: &Env, client: &PredictionMarketContractClient, users: &[Address]) {
+    let balances: i128 = users.iter().map(|u| client.get_balance(u)).sum();
+    let escrow: i128 = (1..=client.get_config().market_counter).map(|id| client.get_market(&id).escrow).sum();
+    assert_eq!(balances + client.total_liquidity() + escrow, token_balance(env, client, &client.address));
+}
+
+#[test]
+fn test_losing_stakes_and_fees_flow_into_pool() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let winner = Address::generate(&env);
+    let loser = Address::generate(&env);
+    let trader = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    for user in [&winner, &loser, &trader] {
+        mint(&env, &client, user, 1_000_000);
+        client.deposit(user, &1_000_000);
+    }
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&trader, &1, &2, &1_000_000, &0, &None);
+    assert_eq!(client.get_market(&1).escrow, 1_000_000);
+    assert_eq!(client.total_liquidity(), 10_000_000);
+
+    // Cash-out: the stake's cost leaves escrow for the pool, which pays the net exit amount
+    client.cash_out(&trader, &1, &0, &None);
+    let net = client.get_balance(&trader);
+    assert_eq!(client.get_market(&1).escrow, 0);
+    assert_eq!(client.total_liquidity(), 10_000_000 + 1_000_000 - net);
+
+    client.place_stake(&winner, &1, &0, &1_000_000, &0, &None);
+    client.place_stake(&loser, &1, &1, &1_000_000, &0, &None);
+    let pool_before = client.total_liquidity();
+    client.settle_market(&admin, &1, &0);
+    let payout = client.get_balance(&winner);
+    assert_eq!(client.total_liquidity(), pool_before + 2_000_000 - payout); // the loser's stake stays in the pool
+    assert_eq!(client.get_market(&1).escrow, 0);
+}
+
+#[test]
+fn test_collateral_invariant_holds_across_flows() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let mut users = std::vec::Vec::new();
+    for _ in 0..6 {
+        let user = Address::generate(&env);
+        mint(&env, &client, &user, 5_000_000);
+        client.deposit(&user, &5_000_000);
+        users.push(user);
+    }
+    let lp = Address::generate(&env);
+    mint(&env, &client, &lp, 50_000_000);
+    client.provide_liquidity(&lp, &50_000_000);
+    assert_collateral_invariant(&env, &client, &users);
+
+    // Push-settled, pull-resolved and voided markets, with cash-outs along the way
+    for _ in 0..3 {
+        client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    }
+    let mut stake_id = 0u64;
+    for market_id in 1..=3u64 {
+        for (i, user) in users.iter().enumerate() {
+            client.place_stake(user, &market_id, &((i % 3) as u32), &(300_000 + i as i128 * 50_000), &0, &None);
+            stake_id += 1;
+            assert_collateral_invariant(&env, &client, &users);
+        }
+        client.cash_out(&users[0], &(stake_id - 5), &0, &None);
+        assert_collateral_invariant(&env, &client, &users);
+    }
+    client.settle_market(&admin, &1, &0);
+    assert_collateral_invariant(&env, &client, &users);
+    client.resolve_market(&admin, &2, &1);
+    assert_collateral_invariant(&env, &client, &users);
+    client.claim(&users[1], &8);
+    assert_collateral_invariant(&env, &client, &users);
+    client.void_market(&admin, &3, &soroban_sdk::symbol_short!("cancelled"));
+    assert_collateral_invariant(&env, &client, &users);
+    client.claim_all(&users[4], &2);
+    client.withdraw(&users[2], &1_000_000);
+    client.withdraw_liquidity(&lp, &10_000_000);
+    assert_collateral_invariant(&env, &client, &users);
+    assert_eq!(client.get_market(&2).escrow, client.get_claimable(&users[1], &2) + client.get_claimable(&users[4], &2));
+}
+
+// DEPTH TESTS
+
+#[test]
+fn test_price_impact_scales_with_depth() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Friendly"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &10_000_000, &PricingModel::ReserveRatio);
+    client.create_market(&admin, &match_metadata(&env, "Final"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &10_000_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.get_current_odds(&1), client.get_current_odds(&2));
+
+    let (shallow_shares, shallow_price, shallow_after) = client.quote_stake(&1, &0, &1_000_000);
+    let (deep_shares, deep_price, deep_after) = client.quote_stake(&2, &0, &1_000_000);
+    let start = client.get_current_odds(&1).get(0).unwrap();
+    assert!(shallow_after - start > 500 * (deep_after - start));
+    assert!(shallow_price > deep_price);
+    assert!(shallow_shares < deep_shares);
+}
+
+#[test]
+fn test_set_market_depth() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 50_000_000);
+    client.provide_liquidity(&admin, &50_000_000);
+    mint(&env, &client, &user, 5_000_000);
+    client.deposit(&user, &5_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &10_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &2_000_000, &0, &None);
+    let odds = three_way_odds(&client, 1);
+    let slippage_before = client.quote_stake(&1, &1, &1_000_000).2 - odds[1];
+
+    client.set_market_depth(&admin, &1, &1_000_000_000);
+    assert_eq!(env.events().all(), vec![&env, contract_event(&env, &client, &MarketDepthUpdated { market_id: 1, depth: 1_000_000_000 })]);
+    let market = client.get_market(&1);
+    assert_eq!(market.depth, 1_000_000_000);
+    assert!(market.reserves.iter().sum::<i128>() > 999_999_990);
+    let rescaled = three_way_odds(&client, 1);
+    assert!((rescaled[0] - odds[0]).abs() <= 1 && (rescaled[1] - odds[1]).abs() <= 1);
+    assert!(client.quote_stake(&1, &1, &1_000_000).2 - rescaled[1] < slippage_before / 50);
+}
+
+#[test]
+fn test_invalid_depth_rejected() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let attacker = Address::generate(&env);
+    let metadata = match_metadata(&env, "Test");
+    assert_eq!(client.try_create_market(&admin, &metadata, &1234567890, &vec![&env, 400_000, 250_000, 340_000], &0, &PricingModel::ReserveRatio), Err(Ok(Error::InvalidDepth)));
+    client.create_market(&admin, &metadata, &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_set_market_depth(&admin, &1, &999_999), Err(Ok(Error::InvalidDepth)));
+    assert_eq!(client.try_set_market_depth(&attacker, &1, &10_000_000), Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.try_set_market_depth(&admin, &2, &10_000_000), Err(Ok(Error::MarketNotFound)));
+}
+
+// LMSR TESTS
+
+#[test]
+fn test_lmsr_fixed_point_math() {
+    const WAD: i128 = 1_000_000_000_000_000_000;
+    assert_eq!(lmsr::exp_wad(0), Ok(WAD));
+    assert!((lmsr::exp_wad(WAD).unwrap() - 2_718_281_828_459_045_235).abs() < 1_000);
+    assert!((lmsr::exp_wad(-3 * WAD).unwrap() - 49_787_068_367_863_942).abs() < 1_000);
+    assert_eq!(lmsr::ln_wad(WAD), Ok(0));
+    assert!((lmsr::ln_wad(3 * WAD).unwrap() - 1_098_612_288_668_109_691).abs() < 1_000);
+    assert!((lmsr::ln_wad(WAD / 4).unwrap() + 1_386_294_361_119_890_618).abs() < 1_000);
+    assert_eq!(lmsr::ln_wad(0), Err(Error::Overflow));
+    assert_eq!(lmsr::exp_wad(41 * WAD), Err(Error::Overflow));
+}
+
+fn create_lmsr_market(env: &Env, depth: i128) -> (Address, PredictionMarketContractClient<'_>) {
+    let (admin, client) = create_admin_and_client(env);
+    mint(env, &client, &admin, 100_000_000);
+    client.provide_liquidity(&admin, &100_000_000);
+    for _ in 0..2 {
+        client.create_market(&admin, &match_metadata(env, "Test"), &1234567890, &vec![env, 400_000, 250_000, 340_000], &depth, &PricingModel::Lmsr);
+    }
+    (admin, client)
+}
+
+#[test]
+fn test_lmsr_prices_follow_odds_and_sum_to_one() {
+    let env = Env::default();
+    let (_admin, client) = create_lmsr_market(&env, 10_000_000);
+    let [home, draw, away] = three_way_odds(&client, 1);
+    assert!((home - 404_040).abs() <= 2 && (draw - 252_525).abs() <= 2 && (away - 343_434).abs() <= 2);
+
+    let user = Address::generate(&env);
+    mint(&env, &client, &user, 10_000_000);
+    client.deposit(&user, &10_000_000);
+    for (outcome, amount) in [(0u32, 3_000_000i128), (2, 1_000_000), (1, 500_000), (0, 2_000_000)] {
+        let (_, _, price_after) = client.quote_stake(&1, &outcome, &amount);
+        client.place_stake(&user, &1, &outcome, &amount, &0, &None);
+        let [home, draw, away] = three_way_odds(&client, 1);
+        assert!((home + draw + away - 1_000_000).abs() <= 3);
+        assert_eq!(price_after, [home, draw, away][outcome as usize]);
+    }
+    assert!(client.get_current_odds(&1).get(0).unwrap() > 500_000);
+}
+
+#[test]
+fn test_lmsr_is_path_independent() {
+    let env = Env::default();
+    let (_admin, client) = create_lmsr_market(&env, 10_000_000);
+    let user = Address::generate(&env);
+    mint(&env, &client, &user, 10_000_000);
+    client.deposit(&user, &10_000_000);
+
+    client.place_stake(&user, &1, &0, &4_000_000, &0, &None);
+    for _ in 0..4 {
+        client.place_stake(&user, &2, &0, &1_000_000, &0, &None);
+    }
+    let whole = client.get_stake(&1).amount;
+    let pieces: i128 = (2..=5).map(|id| client.get_stake(&id).amount).sum();
+    assert!((whole - pieces).abs() <= 4);
+    assert_eq!(client.get_current_odds(&1), client.get_current_odds(&2));
+}
+
+#[test]
+fn test_lmsr_cash_out_returns_cost_when_price_unchanged() {
+    let env = Env::default();
+    let (_admin, client) = create_lmsr_market(&env, 10_000_000);
+    let user = Address::generate(&env);
+    mint(&env, &client, &user, 2_000_000);
+    client.deposit(&user, &2_000_000);
+    let odds_before = client.get_current_odds(&1);
+    client.place_stake(&user, &1, &1, &2_000_000, &0, &None);
+    let (gross, fee, net) = client.quote_cash_out(&1);
+    assert!((gross - 2_000_000).abs() <= 2);
+    client.cash_out(&user, &1, &net, &None);
+    assert_eq!(client.get_balance(&user), net);
+    assert_eq!(fee, gross * 5 / 100);
+    let odds_after = client.get_current_odds(&1);
+    assert!((odds_after.get(1).unwrap() - odds_before.get(1).unwrap()).abs() <= 1);
+}
+
+#[test]
+fn test_lmsr_loss_bounded_by_b_ln_n() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let b = 5_000_000;
+    mint(&env, &client, &admin, 100_000_000);
+    client.provide_liquidity(&admin, &100_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 330_000, 330_000, 330_000], &b, &PricingModel::Lmsr);
+    let user = Address::generate(&env);
+    mint(&env, &client, &user, 50_000_000);
+    client.deposit(&user, &50_000_000);
+
+    // Buying the eventual winner as hard as possible still costs the pool at most b * ln(3)
+    for _ in 0..10 {
+        client.place_stake(&user, &1, &0, &5_000_000, &0, &None);
+    }
+    client.settle_market(&admin, &1, &0);
+    let b_ln_3 = b * 1_098_612 / 1_000_000;
+    let pool_loss = 100_000_000 - client.total_liquidity();
+    assert!(pool_loss > b_ln_3 * 9 / 10);
+    assert!(pool_loss <= b_ln_3);
+}
+
+#[test]
+fn test_lmsr_update_odds_and_depth() {
+    let env = Env::default();
+    let (admin, client) = create_lmsr_market(&env, 10_000_000);
+    client.update_odds(&admin, &1, &vec![&env, 200_000, 250_000, 540_000]);
+    let [home, draw, away] = three_way_odds(&client, 1);
+    assert!((home - 202_020).abs() <= 2 && (draw - 252_525).abs() <= 2 && (away - 545_454).abs() <= 2);
+
+    let shallow = client.quote_stake(&1, &0, &1_000_000).0;
+    client.set_market_depth(&admin, &1, &100_000_000);
+    let rescaled = three_way_odds(&client, 1);
+    assert!((rescaled[0] - home).abs() <= 2 && (rescaled[2] - away).abs() <= 2);
+    assert!(client.quote_stake(&1, &0, &1_000_000).0 > shallow);
+}
+
+#[test]
+fn test_lmsr_stake_much_larger_than_depth() {
+    let env = Env::default();
+    let (_admin, client) = create_lmsr_market(&env, 1_000_000);
+    // 50x the depth: the outcome ends up priced at ~$1 and each further dollar buys ~one share
+    let (shares, avg_price, price_after) = client.quote_stake(&1, &2, &50_000_000);
+    let b_ln_price = 1_068_759; // b * ln(1 / 0.3434) for b = $1: shares gained while the price climbs to $1
+    assert!((shares - (50_000_000 + b_ln_price)).abs() <= 10);
+    assert!(avg_price < 1_000_000);
+    assert_eq!(price_after, 1_000_000);
+}
+
+// ODDS UPDATE TESTS
+
+#[test]
+fn test_update_odds_reseeds_reserves() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 50_000_000);
+    client.provide_liquidity(&admin, &50_000_000);
+    mint(&env, &client, &user, 5_000_000);
+    client.deposit(&user, &5_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &5_000_000, &0, &None);
+    let before = client.get_market(&1);
+    let total_before: i128 = before.reserves.iter().sum();
+    let quote_before = client.quote_stake(&1, &2, &1_000_000);
+
+    client.update_odds(&admin, &1, &vec![&env, 200_000, 250_000, 540_000]);
+    let after = client.get_market(&1);
+    let total_after: i128 = after.reserves.iter().sum();
+    assert!(total_before - total_after < 3); // depth preserved up to rounding
+    assert_eq!(after.reserves.get(0).unwrap(), total_before * 200_000 / 990_000);
+    let [home, draw, away] = three_way_odds(&client, 1);
+    assert_eq!((home, draw, away), (202_020, 252_525, 545_454));
+
+    // New stakes are priced off the updated odds
+    let quote_after = client.quote_stake(&1, &2, &1_000_000);
+    assert!(quote_after.1 > quote_before.1);
+    assert!(quote_after.0 < quote_before.0);
+}
+
+#[test]
+fn test_update_odds_requires_active_market() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_update_odds(&admin, &1, &vec![&env, 300_000, 300_000, 400_000]), Err(Ok(Error::InvalidOddsSum)));
+    client.resolve_market(&admin, &1, &0);
+    assert_eq!(client.try_update_odds(&admin, &1, &vec![&env, 300_000, 300_000, 390_000]), Err(Ok(Error::MarketNotActive)));
+}
+
+// SLIPPAGE TESTS
+
+#[test]
+fn test_place_stake_min_shares_out() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    let front_runner = Address::generate(&env);
+    mint(&env, &client, &admin, 20_000_000);
+    client.provide_liquidity(&admin, &20_000_000);
+    for u in [&user, &front_runner] {
+        mint(&env, &client, u, 2_000_000);
+        client.deposit(u, &2_000_000);
+    }
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.create_market(&admin, &match_metadata(&env, "Twin"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &2, &0, &500_000, &0, &None);
+    let expected_shares = client.get_stake(&1).amount; // same fill the user would get on market 1
+
+    // Another stake lands first and moves the price past the user's bound
+    client.place_stake(&front_runner, &1, &0, &1_000_000, &0, &None);
+    assert_eq!(client.try_place_stake(&user, &1, &0, &500_000, &expected_shares, &None), Err(Ok(Error::SlippageExceeded)));
+    assert_eq!(client.get_balance(&user), 1_500_000);
+    client.place_stake(&user, &1, &0, &500_000, &(expected_shares * 9 / 10), &None);
+    assert!(client.get_stake(&3).amount >= expected_shares * 9 / 10);
+}
+
+#[test]
+fn test_cash_out_min_payout() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+    assert_eq!(client.try_cash_out(&user, &1, &1_000_000, &None), Err(Ok(Error::SlippageExceeded)));
+    assert_eq!(client.get_balance(&user), 0);
+    client.cash_out(&user, &1, &900_000, &None);
+    assert!(client.get_balance(&user) >= 900_000);
+}
+
+#[test]
+fn test_deadline_passed() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    env.ledger().with_mut(|li| li.timestamp = 1_000);
+    client.place_stake(&user, &1, &0, &500_000, &0, &Some(1_000));
+    assert_eq!(client.try_place_stake(&user, &1, &0, &500_000, &0, &Some(999)), Err(Ok(Error::DeadlinePassed)));
+    assert_eq!(client.try_cash_out(&user, &1, &0, &Some(999)), Err(Ok(Error::DeadlinePassed)));
+    client.cash_out(&user, &1, &0, &Some(1_000));
+}
+
+// QUOTE TESTS
+
+#[test]
+fn test_quote_stake_matches_execution() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 50_000_000);
+    client.provide_liquidity(&admin, &50_000_000);
+    mint(&env, &client, &user, 5_000_000);
+    client.deposit(&user, &5_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    let fills = [(0u32, 1_000_000i128), (1, 250_000), (0, 700_000), (2, 1_300_000)];
+    for (i, (outcome, amount)) in fills.iter().enumerate() {
+        let (shares, avg_price, price_after) = client.quote_stake(&1, outcome, amount);
+        client.place_stake(&user, &1, outcome, amount, &shares, &None);
+        let stake = client.get_stake(&(i as u64 + 1));
+        assert_eq!((stake.amount, stake.price), (shares, avg_price));
+        assert_eq!(price_after, client.get_current_odds(&1).get(*outcome).unwrap());
+    }
+}
+
+#[test]
+fn test_quote_cash_out_matches_execution() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    let other = Address::generate(&env);
+    mint(&env, &client, &admin, 20_000_000);
+    client.provide_liquidity(&admin, &20_000_000);
+    for u in [&user, &other] {
+        mint(&env, &client, u, 2_000_000);
+        client.deposit(u, &2_000_000);
+    }
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+    client.place_stake(&other, &1, &0, &1_500_000, &0, &None);
+
+    let (gross, fee, net) = client.quote_cash_out(&1);
+    assert_eq!(fee, gross * 5 / 100);
+    client.cash_out(&user, &1, &net, &None);
+    assert_eq!(
+        env.events().all(),
+        vec![&env, contract_event(&env, &client, &CashedOut { market_id: 1, staker: user.clone(), stake_id: 1, gross_payout: gross, fee, net_payout: net })]
+    );
+    assert_eq!(client.get_balance(&user), 1_000_000 + net);
+}
+
+#[test]
+fn test_quote_errors() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    assert_eq!(client.try_quote_stake(&1, &0, &100_000), Err(Ok(Error::MarketNotFound)));
+    assert_eq!(client.try_quote_cash_out(&1), Err(Ok(Error::StakeNotFound)));
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_quote_stake(&1, &3, &100_000), Err(Ok(Error::InvalidOutcome)));
+    assert_eq!(client.try_quote_stake(&1, &0, &0), Err(Ok(Error::InvalidAmount)));
+    client.resolve_market(&admin, &1, &0);
+    assert_eq!(client.try_quote_stake(&1, &0, &100_000), Err(Ok(Error::MarketNotActive)));
+}
+
+// COMPLETE SET TESTS
+
+#[test]
+fn test_mint_complete_set_creates_stake_per_outcome() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    let odds_before = client.get_current_odds(&1);
+
+    let ids = client.mint_complete_set(&user, &1, &1_000_000);
+    assert_eq!(ids, vec![&env, 1u64, 2, 3]);
+    assert_eq!(
+        last_event(&env),
+        vec![&env, contract_event(&env, &client, &CompleteSetMinted { market_id: 1, user: user.clone(), amount: 1_000_000, stake_ids: ids.clone() })]
+    );
+    let mut total_cost = 0;
+    for (outcome, id) in ids.iter().enumerate() {
+        let stake = client.get_stake(&id);
+        assert_eq!((stake.outcome, stake.amount), (outcome as u32, 1_000_000));
+        total_cost += stake.cost;
+    }
+    assert_eq!(total_cost, 1_000_000);
+    assert_eq!(client.get_balance(&user), 0);
+    assert_eq!(client.get_current_odds(&1), odds_before);
+    // A complete set pays out in full whatever the result, so it reserves nothing from the pool
+    assert_eq!(client.get_market_exposure(&1), 0);
+    assert_eq!(client.get_staker_count(&1), 3);
+    assert_collateral_invariant(&env, &client, core::slice::from_ref(&user));
+
+    client.settle_market(&admin, &1, &1);
+    assert_eq!(client.get_balance(&user), 1_000_000);
+    assert_eq!(client.total_liquidity(), 10_000_000);
+}
+
+#[test]
+fn test_cash_out_every_leg_of_complete_set() {
+    for pricing in [PricingModel::ReserveRatio, PricingModel::Lmsr] {
+        let env = Env::default();
+        let (admin, client) = create_admin_and_client(&env);
+        let user = Address::generate(&env);
+        mint(&env, &client, &admin, 100_000_000);
+        client.provide_liquidity(&admin, &100_000_000);
+        mint(&env, &client, &user, 10_000_000);
+        client.deposit(&user, &10_000_000);
+        client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &1_000_000, &pricing);
+        let reserves_before = client.get_market(&1).reserves;
+        let odds_before = client.get_current_odds(&1);
+
+        // Minting funds the reserves the legs are later sold back into
+        let ids = client.mint_complete_set(&user, &1, &10_000_000);
+        let reserves = client.get_market(&1).reserves;
+        for i in 0..3 {
+            assert!(reserves.get(i).unwrap() > reserves_before.get(i).unwrap());
+        }
+        for (odd, before) in client.get_current_odds(&1).iter().zip(odds_before.iter()) {
+            assert!((odd - before).abs() <= 1);
+        }
+
+        for id in ids.iter() {
+            client.cash_out(&user, &id, &0, &None);
+            for price in client.get_current_odds(&1).iter() {
+                assert!((0..=1_000_000).contains(&price));
+            }
+            if pricing == PricingModel::ReserveRatio {
+                assert!(client.get_market(&1).reserves.iter().all(|reserve| reserve >= 0));
+            }
+            assert!(client.get_total_exposure() <= client.total_liquidity());
+        }
+        // Selling the whole set back can never return more than it cost
+        assert!(client.get_balance(&user) < 10_000_000);
+        assert!(client.total_liquidity() > 100_000_000);
+        if pricing == PricingModel::Lmsr {
+            assert_eq!(client.get_market(&1).reserves, reserves_before);
+        }
+        assert_eq!(client.get_market(&1).escrow, 0);
+        assert_collateral_invariant(&env, &client, core::slice::from_ref(&user));
+    }
+}
+
+#[test]
+fn test_redeem_complete_set_pays_one_per_set() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    let ids = client.mint_complete_set(&user, &1, &1_000_000);
+
+    client.redeem_complete_set(&user, &ids, &400_000);
+    assert_eq!(
+        last_event(&env),
+        vec![&env, contract_event(&env, &client, &CompleteSetRedeemed { market_id: 1, user: user.clone(), amount: 400_000 })]
+    );
+    assert_eq!(client.get_balance(&user), 400_000);
+    assert_eq!(client.get_stake(&1).amount, 600_000);
+    let remaining_cost: i128 = ids.iter().map(|id| client.get_stake(&id).cost).sum();
+    assert_eq!(client.get_market(&1).escrow, remaining_cost);
+    assert_collateral_invariant(&env, &client, core::slice::from_ref(&user));
+
+    client.redeem_complete_set(&user, &ids, &600_000);
+    assert_eq!(client.get_balance(&user), 1_000_000);
+    assert_eq!(client.get_market_stakes(&1).len(), 0);
+    assert_eq!(client.get_staker_count(&1), 0);
+    assert_eq!(client.get_market(&1).escrow, 0);
+    assert_eq!(client.total_liquidity(), 10_000_000);
+    assert_collateral_invariant(&env, &client, core::slice::from_ref(&user));
+}
+
+#[test]
+fn test_redeem_complete_set_from_bought_shares() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 3_000_000);
+    client.deposit(&user, &3_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 330_000, 330_000, 330_000], &990_000_000, &PricingModel::Lmsr);
+    // Shares bought on each outcome can be merged back into cash at $1 per set
+    for outcome in 0..3u32 {
+        client.place_stake(&user, &1, &outcome, &300_000, &0, &None);
+    }
+    let shares = (1..=3u64).map(|id| client.get_stake(&id).amount).min().unwrap();
+    let balance_before = client.get_balance(&user);
+    client.redeem_complete_set(&user, &vec![&env, 1u64, 2, 3], &shares);
+    assert_eq!(client.get_balance(&user), balance_before + shares);
+    // LMSR costs don't depend on the order of the fills, so the sets cost at least what they redeem for
+    assert!(shares <= 900_000);
+    assert_collateral_invariant(&env, &client, core::slice::from_ref(&user));
+}
+
+#[test]
+fn test_bought_legs_cannot_be_redeemed_on_reserve_ratio_market() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 100_000_000);
+    client.provide_liquidity(&admin, &100_000_000);
+    mint(&env, &client, &user, 10_000_000);
+    client.deposit(&user, &10_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 330_000, 330_000, 330_000], &10_000_000, &PricingModel::ReserveRatio);
+
+    // Each fill is priced below the curve, so buying every outcome gets sets for less than $1
+    let costs = [4_342_580, 2_985_290, 2_418_210];
+    for (outcome, cost) in costs.iter().enumerate() {
+        client.place_stake(&user, &1, &(outcome as u32), cost, &0, &None);
+    }
+    let shares = 10_000_000;
+    for id in 1..=3u64 {
+        assert_eq!(client.get_stake(&id).amount, shares);
+    }
+    assert!(costs.iter().sum::<i128>() < shares);
+    let balance_before = client.get_balance(&user);
+    let liquidity_before = client.total_liquidity();
+    assert_eq!(client.try_redeem_complete_set(&user, &vec![&env, 1u64, 2, 3], &shares), Err(Ok(Error::UnmintedSet)));
+    assert_eq!(client.get_balance(&user), balance_before);
+    assert_eq!(client.total_liquidity(), liquidity_before);
+}
+
+#[test]
+fn test_complete_set_errors() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    let other = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    for u in [&user, &other] {
+        mint(&env, &client, u, 2_000_000);
+        client.deposit(u, &2_000_000);
+    }
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_mint_complete_set(&user, &1, &0), Err(Ok(Error::InvalidAmount)));
+    assert_eq!(client.try_mint_complete_set(&user, &1, &3_000_000), Err(Ok(Error::InsufficientBalance)));
+    assert_eq!(client.try_mint_complete_set(&user, &2, &1_000_000), Err(Ok(Error::MarketNotFound)));
+
+    let ids = client.mint_complete_set(&user, &1, &1_000_000);
+    client.place_stake(&user, &1, &0, &100_000, &0, &None);
+    assert_eq!(client.try_redeem_complete_set(&user, &vec![&env, 1u64, 2], &100), Err(Ok(Error::IncompleteSet)));
+    assert_eq!(client.try_redeem_complete_set(&user, &vec![&env, 1u64, 4, 3], &100), Err(Ok(Error::IncompleteSet)));
+    assert_eq!(client.try_redeem_complete_set(&user, &ids, &1_000_001), Err(Ok(Error::InsufficientShares)));
+    assert_eq!(client.try_redeem_complete_set(&other, &ids, &100), Err(Ok(Error::NotStakeOwner)));
+    assert_eq!(client.try_redeem_complete_set(&user, &vec![&env, 4u64, 2, 3], &100), Err(Ok(Error::UnmintedSet)));
+
+    client.settle_market(&admin, &1, &0);
+    assert_eq!(client.try_mint_complete_set(&user, &1, &1_000), Err(Ok(Error::MarketNotActive)));
+}
+
+// MULTI-OUTCOME TESTS
+
+#[test]
+fn test_two_outcome_market() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 2_000_000);
+    client.deposit(&user, &2_000_000);
+    let mut metadata = match_metadata(&env, "Wimbledon final");
+    metadata.outcome_labels = labels(&env, &["Sinner", "Alcaraz"]);
+    client.create_market(&admin, &metadata, &1234567890, &vec![&env, 590_000, 400_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.get_market_metadata(&1).outcome_labels.len(), 2);
+    assert_eq!(client.get_current_odds(&1).len(), 2);
+    assert_eq!(client.try_place_stake(&user, &1, &2, &1_000_000, &0, &None), Err(Ok(Error::InvalidOutcome)));
+    assert_eq!(client.try_quote_stake(&1, &2, &1_000_000), Err(Ok(Error::InvalidOutcome)));
+
+    client.place_stake(&user, &1, &1, &1_000_000, &0, &None);
+    let shares = client.get_stake(&1).amount;
+    assert_eq!(client.get_market_exposure(&1), shares - 1_000_000);
+    assert_eq!(client.try_settle_market(&admin, &1, &2), Err(Ok(Error::InvalidOutcome)));
+    client.settle_market(&admin, &1, &1);
+    assert_eq!(client.get_balance(&user), 1_000_000 + shares);
+}
+
+#[test]
+fn test_outright_market_with_many_outcomes() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 50_000_000);
+    client.provide_liquidity(&admin, &50_000_000);
+    mint(&env, &client, &user, 5_000_000);
+    client.deposit(&user, &5_000_000);
+    let mut metadata = match_metadata(&env, "The Open winner");
+    metadata.outcome_labels = Vec::new(&env);
+    let mut odds: Vec<i128> = Vec::new(&env);
+    for i in 0..MAX_OUTCOMES {
+        metadata.outcome_labels.push_back(String::from_str(&env, &std::format!("Player {}", i)));
+        odds.push_back(if i == 0 { 990_000 - 30_000 * (MAX_OUTCOMES as i128 - 1) } else { 30_000 });
+    }
+    client.create_market(&admin, &metadata, &1234567890, &odds, &10_000_000, &PricingModel::Lmsr);
+    let prices = client.get_current_odds(&1);
+    assert_eq!(prices.len(), MAX_OUTCOMES);
+    assert!((prices.iter().sum::<i128>() - 1_000_000).abs() <= MAX_OUTCOMES as i128);
+
+    client.place_stake(&user, &1, &(MAX_OUTCOMES - 1), &1_000_000, &0, &None);
+    assert!(client.get_current_odds(&1).get(MAX_OUTCOMES - 1).unwrap() > prices.get(MAX_OUTCOMES - 1).unwrap());
+    client.cash_out(&user, &1, &0, &None);
+    let ids = client.mint_complete_set(&user, &1, &1_000_000);
+    assert_eq!(ids.len(), MAX_OUTCOMES);
+    client.redeem_complete_set(&user, &ids, &1_000_000);
+    assert_collateral_invariant(&env, &client, core::slice::from_ref(&user));
+}
+
+#[test]
+fn test_market_outcome_count_validation() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let metadata = match_metadata(&env, "Test");
+    let mut one = metadata.clone();
+    one.outcome_labels = labels(&env, &["Only"]);
+    assert_eq!(client.try_create_market(&admin, &one, &1234567890, &vec![&env, 990_000], &990_000_000, &PricingModel::ReserveRatio), Err(Ok(Error::InvalidOutcomeCount)));
+    let mut too_many = metadata.clone();
+    too_many.outcome_labels = Vec::new(&env);
+    let mut odds: Vec<i128> = Vec::new(&env);
+    for _ in 0..=MAX_OUTCOMES {
+        too_many.outcome_labels.push_back(String::from_str(&env, "x"));
+        odds.push_back(30_000);
+    }
+    assert_eq!(client.try_create_market(&admin, &too_many, &1234567890, &odds, &990_000_000, &PricingModel::ReserveRatio), Err(Ok(Error::InvalidOutcomeCount)));
+    // Labels and odds must line up
+    assert_eq!(client.try_create_market(&admin, &metadata, &1234567890, &vec![&env, 590_000, 400_000], &990_000_000, &PricingModel::ReserveRatio), Err(Ok(Error::InvalidOutcomeCount)));
+
+    client.create_market(&admin, &metadata, &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_update_odds(&admin, &1, &vec![&env, 590_000, 400_000]), Err(Ok(Error::InvalidOutcomeCount)));
+}
+
+// METADATA TESTS
+
+#[test]
+fn test_market_metadata_round_trip() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let mut metadata = match_metadata(&env, "Brasil vs Inglaterra – Final");
+    metadata.venue = String::from_str(&env, "Estádio do Maracanã");
+    metadata.image_uri = String::from_str(&env, "ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi");
+    client.create_market(&admin, &metadata, &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.get_market_metadata(&1), metadata);
+    assert_eq!(client.try_get_market_metadata(&2), Err(Ok(Error::MarketNotFound)));
+
+    metadata.competition = String::from_str(&env, "Copa do Mundo");
+    client.update_market_metadata(&admin, &1, &metadata);
+    assert_eq!(env.events().all(), vec![&env, contract_event(&env, &client, &MarketMetadataUpdated { market_id: 1, metadata: metadata.clone() })]);
+    assert_eq!(client.get_market_metadata(&1), metadata);
+}
+
+#[test]
+fn test_update_market_metadata_validation() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let attacker = Address::generate(&env);
+    let metadata = match_metadata(&env, "Test");
+    client.create_market(&admin, &metadata, &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_update_market_metadata(&attacker, &1, &metadata), Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.try_update_market_metadata(&admin, &2, &metadata), Err(Ok(Error::MarketNotFound)));
+
+    let mut untitled = metadata.clone();
+    untitled.title = String::from_str(&env, "");
+    assert_eq!(client.try_update_market_metadata(&admin, &1, &untitled), Err(Ok(Error::InvalidMetadata)));
+    let mut long_uri = metadata.clone();
+    long_uri.image_uri = String::from_str(&env, &"x".repeat(257));
+    assert_eq!(client.try_update_market_metadata(&admin, &1, &long_uri), Err(Ok(Error::InvalidMetadata)));
+    let mut two_labels = metadata.clone();
+    two_labels.outcome_labels = labels(&env, &["Brazil", "England"]);
+    assert_eq!(client.try_update_market_metadata(&admin, &1, &two_labels), Err(Ok(Error::InvalidOutcomeCount)));
+    assert_eq!(client.try_create_market(&admin, &untitled, &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio), Err(Ok(Error::InvalidMetadata)));
+
+    client.settle_market(&admin, &1, &0);
+    assert_eq!(client.try_update_market_metadata(&admin, &1, &metadata), Err(Ok(Error::MarketNotActive)));
+}
+
+// BETTING WINDOW TESTS
+
+#[test]
+fn test_betting_closes_at_start_time() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 2_000_000);
+    client.deposit(&user, &2_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.get_market(&1).close_time, 1234567890);
+
+    env.ledger().set_timestamp(1234567889);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+    env.ledger().set_timestamp(1234567890);
+    assert_eq!(client.try_place_stake(&user, &1, &0, &1_000_000, &0, &None), Err(Ok(Error::BettingClosed)));
+    assert_eq!(client.try_quote_stake(&1, &0, &1_000_000), Err(Ok(Error::BettingClosed)));
+    // Selling back at pre-match prices once the match is under way is just as exploitable
+    assert_eq!(client.try_cash_out(&user, &1, &0, &None), Err(Ok(Error::BettingClosed)));
+    assert_eq!(client.try_quote_cash_out(&1), Err(Ok(Error::BettingClosed)));
+
+    client.settle_market(&admin, &1, &0);
+    assert!(client.get_balance(&user) > 1_000_000);
+}
+
+#[test]
+fn test_complete_sets_close_with_betting() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 2_000_000);
+    client.deposit(&user, &2_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+
+    env.ledger().set_timestamp(1234567889);
+    let ids = client.mint_complete_set(&user, &1, &1_000_000);
+    env.ledger().set_timestamp(1234567890);
+    assert_eq!(client.try_mint_complete_set(&user, &1, &1_000_000), Err(Ok(Error::BettingClosed)));
+    assert_eq!(client.try_redeem_complete_set(&user, &ids, &1_000_000), Err(Ok(Error::BettingClosed)));
+    assert_eq!(client.get_balance(&user), 1_000_000);
+
+    // The set still pays $1 per share at settlement
+    client.settle_market(&admin, &1, &2);
+    assert_eq!(client.get_balance(&user), 2_000_000);
+}
+
+#[test]
+fn test_set_betting_window_with_in_play() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 2_000_000);
+    client.deposit(&user, &2_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1_000_000, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+
+    // Pre-match betting can close early
+    client.set_betting_window(&admin, &1, &900_000, &None);
+    assert_eq!(env.events().all(), vec![&env, contract_event(&env, &client, &BettingWindowUpdated { market_id: 1, close_time: 900_000, in_play_close: None })]);
+    env.ledger().set_timestamp(950_000);
+    assert_eq!(client.try_place_stake(&user, &1, &0, &500_000, &0, &None), Err(Ok(Error::BettingClosed)));
+
+    // In-play markets keep trading after kickoff until their own close
+    client.set_betting_window(&admin, &1, &1_000_000, &Some(1_006_000));
+    env.ledger().set_timestamp(1_005_999);
+    client.place_stake(&user, &1, &0, &500_000, &0, &None);
+    client.cash_out(&user, &1, &0, &None);
+    env.ledger().set_timestamp(1_006_000);
+    assert_eq!(client.try_place_stake(&user, &1, &0, &500_000, &0, &None), Err(Ok(Error::BettingClosed)));
+
+    // Reopening betting is an admin decision, e.g. after a postponement
+    client.set_betting_window(&admin, &1, &1_000_000, &Some(2_000_000));
+    client.place_stake(&user, &1, &0, &500_000, &0, &None);
+}
+
+#[test]
+fn test_set_betting_window_validation() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let attacker = Address::generate(&env);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1_000_000, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_set_betting_window(&attacker, &1, &900_000, &None), Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.try_set_betting_window(&admin, &2, &900_000, &None), Err(Ok(Error::MarketNotFound)));
+    assert_eq!(client.try_set_betting_window(&admin, &1, &1_000_001, &None), Err(Ok(Error::InvalidBettingWindow)));
+    assert_eq!(client.try_set_betting_window(&admin, &1, &1_000_000, &Some(1_000_000)), Err(Ok(Error::InvalidBettingWindow)));
+    client.settle_market(&admin, &1, &0);
+    assert_eq!(client.try_set_betting_window(&admin, &1, &900_000, &None), Err(Ok(Error::MarketNotActive)));
+}
+
+// PAUSE TESTS
+
+fn setup_pausable_market(env: &Env) -> (Address, PredictionMarketContractClient<'_>, Address, Address) {
+    let (admin, client) = create_admin_and_client(env);
+    let guardian = Address::generate(env);
+    let user = Address::generate(env);
+    client.grant_role(&admin, &Role::Guardian, &guardian);
+    mint(env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(env, &client, &user, 3_000_000);
+    client.deposit(&user, &3_000_000);
+    client.create_market(&admin, &match_metadata(env, "Test"), &1234567890, &vec![env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &1_000_000, &0, &None);
+    (admin, client, guardian, user)
+}
+
+#[test]
+fn test_pause_blocks_trading_and_liquidity() {
+    let env = Env::default();
+    let (admin, client, guardian, user) = setup_pausable_market(&env);
+    let ids = client.mint_complete_set(&user, &1, &300_000);
+
+    client.pause(&guardian);
+    assert_eq!(env.events().all(), vec![&env, contract_event(&env, &client, &ContractPaused { by: guardian.clone() })]);
+    assert!(client.get_config().paused);
+    assert_eq!(client.try_place_stake(&user, &1, &0, &100_000, &0, &None), Err(Ok(Error::ContractPaused)));
+    assert_eq!(client.try_quote_stake(&1, &0, &100_000), Err(Ok(Error::ContractPaused)));
+    assert_eq!(client.try_cash_out(&user, &1, &0, &None), Err(Ok(Error::ContractPaused)));
+    assert_eq!(client.try_quote_cash_out(&1), Err(Ok(Error::ContractPaused)));
+    assert_eq!(client.try_mint_complete_set(&user, &1, &100_000), Err(Ok(Error::ContractPaused)));
+    assert_eq!(client.try_redeem_complete_set(&user, &ids, &100_000), Err(Ok(Error::ContractPaused)));
+    assert_eq!(client.try_provide_liquidity(&admin, &1_000_000), Err(Ok(Error::ContractPaused)));
+    assert_eq!(client.try_withdraw_liquidity(&admin, &1_000_000), Err(Ok(Error::ContractPaused)));
+
+    // Free balances stay withdrawable
+    let free = client.get_balance(&user);
+    client.withdraw(&user, &free);
+    assert_eq!(client.get_balance(&user), 0);
+
+    client.unpause(&admin);
+    assert_eq!(env.events().all(), vec![&env, contract_event(&env, &client, &ContractUnpaused { by: admin.clone() })]);
+    client.cash_out(&user, &1, &0, &None);
+    client.withdraw_liquidity(&admin, &1_000_000);
+}
+
+#[test]
+fn test_suspended_market_blocks_trading() {
+    let env = Env::default();
+    let (admin, client, guardian, user) = setup_pausable_market(&env);
+    client.create_market(&admin, &match_metadata(&env, "Other"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+
+    client.suspend_market(&guardian, &1);
+    assert_eq!(env.events().all(), vec![&env, contract_event(&env, &client, &MarketSuspended { market_id: 1, by: guardian.clone() })]);
+    assert_eq!(client.get_market(&1).status, MarketStatus::Suspended);
+    assert_eq!(client.try_place_stake(&user, &1, &0, &100_000, &0, &None), Err(Ok(Error::MarketNotActive)));
+    assert_eq!(client.try_cash_out(&user, &1, &0, &None), Err(Ok(Error::MarketNotActive)));
+    assert_eq!(client.try_mint_complete_set(&user, &1, &100_000), Err(Ok(Error::MarketNotActive)));
+    assert_eq!(client.try_suspend_market(&guardian, &1), Err(Ok(Error::MarketNotActive)));
+    // Other markets and liquidity are unaffected
+    client.place_stake(&user, &2, &1, &100_000, &0, &None);
+    mint(&env, &client, &admin, 1_000_000);
+    client.provide_liquidity(&admin, &1_000_000);
+
+    // The admin can fix the market while it is suspended
+    client.update_odds(&admin, &1, &vec![&env, 300_000, 300_000, 390_000]);
+    client.resume_market(&guardian, &1);
+    assert_eq!(env.events().all(), vec![&env, contract_event(&env, &client, &MarketResumed { market_id: 1, by: guardian.clone() })]);
+    client.place_stake(&user, &1, &0, &100_000, &0, &None);
+    assert_eq!(client.try_resume_market(&guardian, &1), Err(Ok(Error::MarketNotActive)));
+}
+
+#[test]
+fn test_suspended_market_can_be_settled_or_voided() {
+    let env = Env::default();
+    let (admin, client, guardian, user) = setup_pausable_market(&env);
+    client.suspend_market(&guardian, &1);
+    client.void_market(&admin, &1, &soroban_sdk::symbol_short!("oracle"));
+    assert_eq!(client.get_market(&1).status, MarketStatus::Archived);
+    assert_eq!(client.get_balance(&user), 3_000_000);
+
+    client.create_market(&admin, &match_metadata(&env, "Test2"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &2, &0, &1_000_000, &0, &None);
+    client.suspend_market(&admin, &2);
+    client.settle_market(&admin, &2, &0);
+    assert_eq!(client.get_market(&2).status, MarketStatus::Settled);
+}
+
+#[test]
+fn test_pause_controls_require_guardian_or_admin() {
+    let env = Env::default();
+    let (admin, client, guardian, user) = setup_pausable_market(&env);
+    assert_eq!(client.try_pause(&user), Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.try_unpause(&user), Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.try_suspend_market(&user, &1), Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.try_resume_market(&user, &1), Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.try_grant_role(&guardian, &Role::Guardian, &user), Err(Ok(Error::Unauthorized)));
+    // The guardian's powers stop at the emergency controls
+    assert_eq!(client.try_update_odds(&guardian, &1, &vec![&env, 300_000, 300_000, 390_000]), Err(Ok(Error::Unauthorized)));
+
+    client.revoke_role(&admin, &Role::Guardian, &guardian);
+    assert_eq!(client.try_pause(&guardian), Err(Ok(Error::Unauthorized)));
+    client.pause(&admin);
+    assert!(client.get_config().paused);
+}
+
+// ADMIN TRANSFER TESTS
+
+#[test]
+fn test_two_step_admin_transfer() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let new_admin = Address::generate(&env);
+    let stranger = Address::generate(&env);
+
+    client.propose_admin(&admin, &new_admin);
+    assert_eq!(
+        env.events().all(),
+        vec![&env, contract_event(&env, &client, &AdminTransferProposed { admin: admin.clone(), new_admin: new_admin.clone() })]
+    );
+    // Nothing changes until the nominee accepts
+    assert_eq!(client.get_admin(), admin);
+    assert_eq!(client.get_pending_admin(), Some(new_admin.clone()));
+    assert_eq!(client.try_accept_admin(&stranger), Err(Ok(Error::NoPendingAdmin)));
+    // Accepting needs the nominee's own signature
+    assert!(client.mock_auths(&[]).try_accept_admin(&new_admin).is_err());
+
+    client.accept_admin(&new_admin);
+    assert_eq!(
+        env.events().all(),
+        vec![&env, contract_event(&env, &client, &AdminTransferred { previous_admin: admin.clone(), new_admin: new_admin.clone() })]
+    );
+    assert_eq!(client.get_admin(), new_admin);
+    assert_eq!(client.get_pending_admin(), None);
+    assert_eq!(client.try_create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio), Err(Ok(Error::Unauthorized)));
+    client.create_market(&new_admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    assert_eq!(client.try_accept_admin(&new_admin), Err(Ok(Error::NoPendingAdmin)));
+}
+
+#[test]
+fn test_cancel_admin_transfer() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let new_admin = Address::generate(&env);
+    assert_eq!(client.try_propose_admin(&new_admin, &new_admin), Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.try_cancel_admin_transfer(&admin), Err(Ok(Error::NoPendingAdmin)));
+
+    client.propose_admin(&admin, &new_admin);
+    assert_eq!(client.try_cancel_admin_transfer(&new_admin), Err(Ok(Error::Unauthorized)));
+    client.cancel_admin_transfer(&admin);
+    assert_eq!(
+        env.events().all(),
+        vec![&env, contract_event(&env, &client, &AdminTransferCancelled { admin: admin.clone(), new_admin: new_admin.clone() })]
+    );
+    assert_eq!(client.get_pending_admin(), None);
+    assert_eq!(client.try_accept_admin(&new_admin), Err(Ok(Error::NoPendingAdmin)));
+    assert_eq!(client.get_admin(), admin);
+}
+
+// ROLE TESTS
+
+#[test]
+fn test_grant_and_revoke_role() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let trader = Address::generate(&env);
+    assert!(client.has_role(&Role::OddsManager, &admin));
+    assert!(!client.has_role(&Role::OddsManager, &trader));
+
+    client.grant_role(&admin, &Role::OddsManager, &trader);
+    assert_eq!(env.events().all(), vec![&env, contract_event(&env, &client, &RoleGranted { role: Role::OddsManager, account: trader.clone() })]);
+    assert!(client.has_role(&Role::OddsManager, &trader));
+    assert!(!client.has_role(&Role::Settler, &trader));
+
+    client.revoke_role(&admin, &Role::OddsManager, &trader);
+    assert_eq!(env.events().all(), vec![&env, contract_event(&env, &client, &RoleRevoked { role: Role::OddsManager, account: trader.clone() })]);
+    assert!(!client.has_role(&Role::OddsManager, &trader));
+    assert_eq!(client.try_grant_role(&trader, &Role::OddsManager, &trader), Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.try_revoke_role(&trader, &Role::OddsManager, &admin), Err(Ok(Error::Unauthorized)));
+}
+
+#[test]
+fn test_roles_gate_operator_duties() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let creator = Address::generate(&env);
+    let trader = Address::generate(&env);
+    let results_desk = Address::generate(&env);
+    let finance = Address::generate(&env);
+    client.grant_role(&admin, &Role::MarketCreator, &creator);
+    client.grant_role(&admin, &Role::OddsManager, &trader);
+    client.grant_role(&admin, &Role::Settler, &results_desk);
+    client.grant_role(&admin, &Role::Treasurer, &finance);
+    let metadata = match_metadata(&env, "Test");
+    let odds = vec![&env, 400_000, 250_000, 340_000];
+
+    assert_eq!(client.try_create_market(&trader, &metadata, &1234567890, &odds, &990_000_000, &PricingModel::ReserveRatio), Err(Ok(Error::Unauthorized)));
+    client.create_market(&creator, &metadata, &1234567890, &odds, &990_000_000, &PricingModel::ReserveRatio);
+    client.create_market(&creator, &metadata, &1234567890, &odds, &990_000_000, &PricingModel::ReserveRatio);
+    client.update_market_metadata(&creator, &1, &metadata);
+    client.set_betting_window(&creator, &1, &1234567000, &None);
+
+    let new_odds = vec![&env, 300_000, 300_000, 390_000];
+    assert_eq!(client.try_update_odds(&creator, &1, &new_odds), Err(Ok(Error::Unauthorized)));
+    client.update_odds(&trader, &1, &new_odds);
+    client.set_market_depth(&trader, &1, &10_000_000);
+
+    assert_eq!(client.try_settle_market(&trader, &1, &0), Err(Ok(Error::Unauthorized)));
+    client.settle_market(&results_desk, &1, &0);
+    client.archive_market(&results_desk, &1);
+    client.void_market(&results_desk, &2, &soroban_sdk::symbol_short!("postponed"));
+
+    assert_eq!(client.try_set_cashout_fee(&results_desk, &3), Err(Ok(Error::Unauthorized)));
+    client.set_cashout_fee(&finance, &3);
+    assert_eq!(client.get_config().cashout_fee_percent, 3);
+    // Delegated roles don't include admin powers
+    assert_eq!(client.try_grant_role(&finance, &Role::Settler, &finance), Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.try_propose_admin(&creator, &creator), Err(Ok(Error::Unauthorized)));
+}
+
+// STORAGE TTL TESTS
+
+fn advance_ledgers(env: &Env, ledgers: u32) {
+    env.ledger().with_mut(|li| li.sequence_number += ledgers);
+}
+
+fn persistent_ttl<K: IntoVal<Env, Val>>(env: &Env, client: &PredictionMarketContractClient, key: &K) -> u32 {
+    env.as_contract(&client.address, || env.storage().persistent().get_ttl(key))
+}
+
+#[test]
+fn test_touched_entries_get_extended_ttl() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &500_000, &0, &None);
+
+    assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::user_key(&user)), BALANCE_BUMP_AMOUNT);
+    assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::lp_key(&admin)), BALANCE_BUMP_AMOUNT);
+    assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::market_key(1)), MARKET_BUMP_AMOUNT);
+    assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::market_metadata_key(1)), MARKET_BUMP_AMOUNT);
+    assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::market_stakes_key(1, 0)), MARKET_BUMP_AMOUNT);
+    assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::stake_key(1)), MARKET_BUMP_AMOUNT);
+    assert_eq!(env.as_contract(&client.address, || env.storage().instance().get_ttl()), INSTANCE_BUMP_AMOUNT);
+}
+
+#[test]
+fn test_open_market_survives_past_default_ttl_with_bumps() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000);
+    client.provide_liquidity(&admin, &10_000_000);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    client.place_stake(&user, &1, &0, &500_000, &0, &None);
+    let stake = client.get_stake(&1);
+
+    // 60 days pass with a keeper bumping the market every 5 days
+    for _ in 0..12 {
+        advance_ledgers(&env, 5 * DAY_IN_LEDGERS);
+        assert_eq!(client.bump_market(&1, &0, &10), 0);
+    }
+    assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::stake_key(1)), MARKET_BUMP_AMOUNT);
+    assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::market_metadata_key(1)), MARKET_BUMP_AMOUNT);
+    assert_eq!(client.get_stake(&1), stake);
+    assert_eq!(client.get_market(&1).status, MarketStatus::Active);
+    assert_eq!(client.get_market_stakes(&1).len(), 1);
+}
+
+#[test]
+fn test_balance_ttl_extended_when_touched() {
+    let env = Env::default();
+    let (_admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &user, 1_000_000);
+    client.deposit(&user, &1_000_000);
+
+    advance_ledgers(&env, 10 * DAY_IN_LEDGERS);
+    let ttl_before = persistent_ttl(&env, &client, &PredictionMarketContract::user_key(&user));
+    assert_eq!(ttl_before, BALANCE_BUMP_AMOUNT - 10 * DAY_IN_LEDGERS);
+    assert!(ttl_before < BALANCE_LIFETIME_THRESHOLD);
+    client.withdraw(&user, &400_000);
+    assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::user_key(&user)), BALANCE_BUMP_AMOUNT);
+    assert_eq!(client.get_balance(&user), 600_000);
+}
+
+#[test]
+fn test_role_grants_kept_in_persistent_storage() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let guardian = Address::generate(&env);
+    client.grant_role(&admin, &Role::Guardian, &guardian);
+    let role_key = PredictionMarketContract::role_key(Role::Guardian, &guardian);
+    // Grants stay out of the instance, which every call loads
+    assert!(!env.as_contract(&client.address, || env.storage().instance().has(&role_key)));
+    assert_eq!(persistent_ttl(&env, &client, &role_key), ROLE_BUMP_AMOUNT);
+
+    // Using the role extends it again; the admin keeps the contract itself alive meanwhile
+    for _ in 0..20 {
+        advance_ledgers(&env, 5 * DAY_IN_LEDGERS);
+        client.set_cashout_fee(&admin, &5);
+    }
+    assert_eq!(persistent_ttl(&env, &client, &role_key), ROLE_BUMP_AMOUNT - 100 * DAY_IN_LEDGERS);
+    client.pause(&guardian);
+    assert_eq!(persistent_ttl(&env, &client, &role_key), ROLE_BUMP_AMOUNT);
+    client.revoke_role(&admin, &Role::Guardian, &guardian);
+    assert!(!client.has_role(&Role::Guardian, &guardian));
+    assert_eq!(client.try_unpause(&guardian), Err(Ok(Error::Unauthorized)));
+}
+
+#[test]
+fn test_bump_full_market_in_batches() {
+    let env = Env::default();
+    let (admin, client) = create_admin_and_client(&env);
+    let user = Address::generate(&env);
+    mint(&env, &client, &admin, 10_000_000_000);
+    client.provide_liquidity(&admin, &10_000_000_000);
+    mint(&env, &client, &user, 100_000_000);
+    client.deposit(&user, &100_000_000);
+    client.create_market(&admin, &match_metadata(&env, "Test"), &1234567890, &vec![&env, 400_000, 250_000, 340_000], &990_000_000, &PricingModel::ReserveRatio);
+    for i in 0..MAX_STAKERS_PER_MARKET {
+        client.place_stake(&user, &1, &(i % 3), &100_000, &0, &None);
+    }
+    advance_ledgers(&env, 5 * DAY_IN_LEDGERS);
+
+    // A keeper walks the stake list; every call must fit in a single transaction's budget
+    let mut start = 0;
+    let mut calls = 0;
+    loop {
+        env.cost_estimate().budget().reset_default();
+        let remaining = client.bump_market(&1, &start, &1000);
+        calls += 1;
+        start += MAX_BUMP_BATCH;
+        if remaining == 0 { break; }
+    }
+    assert_eq!(calls, MAX_STAKERS_PER_MARKET.div_ceil(MAX_BUMP_BATCH));
+    assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::market_key(1)), MARKET_BUMP_AMOUNT);
+    for page in 0..MAX_STAKERS_PER_MARKET.div_ceil(STAKE_PAGE_SIZE) {
+        assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::market_stakes_key(1, page)), MARKET_BUMP_AMOUNT);
+    }
+    for stake_id in 1..=MAX_STAKERS_PER_MARKET as u64 {
+        assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::stake_key(stake_id)), MARKET_BUMP_AMOUNT);
+    }
+    // A batch only reaches the stakes it covers, and a start past the end is a no-op
+    advance_ledgers(&env, 5 * DAY_IN_LEDGERS);
+    assert_eq!(client.bump_market(&1, &10, &5), MAX_STAKERS_PER_MARKET - 15);
+    assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::stake_key(11)), MARKET_BUMP_AMOUNT);
+    assert_eq!(persistent_ttl(&env, &client, &PredictionMarketContract::stake_key(16)), MARKET_BUMP_AMOUNT - 5 * DAY_IN_LEDGERS);
+    assert_eq!(client.bump_market(&1, &5000, &5), 0);
+    assert_eq!(client.try_bump_market(&1, &0, &0), Err(Ok(Error::InvalidAmount)));
+}
+
+#[test]
+fn test_bump_market_nonexistent() {
+    let env = Env::default();
+    let (_admin, client) = create_admin_and_client(&env);
+    assert_eq!(client.try_bump_market(&999, &0, &10), Err(Ok(Error::MarketNotFound)));
+}
@@ -4,7 +4,9 @@ const CONFIG = {
     contractId: 'CCVXQQ3EKH25D5DNFJMR45UGKCSX33STC5CTNVXVFHTWNZZOXJ6XQ4CS',
     rpcUrl: 'https://soroban-testnet.stellar.org',
     networkPassphrase: StellarSdk.Networks.TESTNET,
-    decimals: 1000000 // 6 decimal places
+    decimals: 1000000, // 6 decimal places
+    slippageBps: 100, // accept fills up to 1% worse than the quote
+    settleBatchSize: 25 // stakes per settle_batch call, the contract's cap
 };
 
 // Global variables
@@ -50,6 +52,11 @@ function formatAddress(address) {
     return `${address.slice(0, 6)}…${address.slice(-4)}`;
 }
 
+function escapeHtml(text) {
+    // Market titles and labels are free-form strings set on-chain
+    return String(text).replace(/[&<>"']/g, c => ({ '&': '&amp;', '<': '&lt;', '>': '&gt;', '"': '&quot;', "'": '&#39;' })[c]);
+}
+
 function formatDateTime(timestamp) {
     // Convert BigInt to Number if needed
     const numTimestamp = typeof timestamp === 'bigint' ? Number(timestamp) : timestamp;
@@ -350,23 +357,63 @@ async function readContract(method, ...args) {
     }
 }
 
-// Contract specific functions
-async function initializeContract() {
-    try {
-        showStatus('Initializing contract...', 'info');
-        const adminAddress = StellarSdk.Address.fromString(keypair.publicKey());
-        await callContract('initialize', adminAddress);
-        showStatus('Contract initialized successfully!', 'success');
-        await checkContractStatus();
-    } catch (error) {
-        console.error('Error initializing contract:', error);
-        showStatus('Failed to initialize contract', 'error');
-    }
+// ScVal builders for argument types the generic conversion above can't infer
+function i128Val(value) {
+    return StellarSdk.nativeToScVal(BigInt(value), { type: 'i128' });
+}
+
+function u32Val(value) {
+    return StellarSdk.nativeToScVal(Number(value), { type: 'u32' });
+}
+
+// Option::None
+function noneVal() {
+    return StellarSdk.xdr.ScVal.scvVoid();
+}
+
+// Unit enum variants are a one-element vec holding the variant name
+function enumVal(variant) {
+    return StellarSdk.xdr.ScVal.scvVec([StellarSdk.xdr.ScVal.scvSymbol(variant)]);
+}
+
+// Structs are maps from field name to value, with the keys in sorted order
+function structVal(fields) {
+    return StellarSdk.xdr.ScVal.scvMap(Object.keys(fields).sort().map(key => new StellarSdk.xdr.ScMapEntry({
+        key: StellarSdk.xdr.ScVal.scvSymbol(key),
+        val: fields[key]
+    })));
 }
 
+function enumName(value) {
+    // Enums are returned as arrays, e.g. ['Active']
+    return Array.isArray(value) ? value[0] : value;
+}
+
+// Reads a market together with its metadata, or null if it doesn't exist
+async function readMarket(marketId) {
+    const market = await readContract('get_market', BigInt(marketId));
+    if (!market) return null;
+
+    const marketData = StellarSdk.scValToNative(market);
+    const metadataResult = await readContract('get_market_metadata', BigInt(marketId));
+    const metadata = metadataResult ? StellarSdk.scValToNative(metadataResult) : {};
+    const outcomeLabels = metadata.outcome_labels || marketData.odds.map((_, index) => `Outcome ${index + 1}`);
+
+    return {
+        ...marketData,
+        id: marketId,
+        status: enumName(marketData.status),
+        settlement: enumName(marketData.settlement),
+        title: metadata.title || `Market ${marketId}`,
+        outcomeLabels,
+        metadata
+    };
+}
+
+// Contract specific functions
 async function checkContractStatus() {
     try {
-        // Check if contract is initialized by calling get_admin
+        // The constructor sets the admin at deploy time, so get_admin succeeds on any deployed contract
         const adminResult = await readContract('get_admin');
         isInitialized = adminResult !== null;
 
@@ -380,15 +427,15 @@ async function checkContractStatus() {
             if (el) el.textContent = `$${formatAmount(liquidityAmount)}`;
         });
 
-        const initializedEl = document.getElementById('contract-initialized');
-        if (initializedEl) {
-            initializedEl.textContent = isInitialized ? 'Yes' : 'No';
+        const adminEl = document.getElementById('contract-admin');
+        if (adminEl) {
+            adminEl.textContent = isInitialized ? formatAddress(StellarSdk.scValToNative(adminResult)) : 'Not deployed';
         }
 
-        // Hide initialize section if already initialized
-        const initSection = document.getElementById('initialize-section');
-        if (initSection && isInitialized) {
-            initSection.style.display = 'none';
+        // Deployment instructions are only needed until the contract exists
+        const deploySection = document.getElementById('deploy-section');
+        if (deploySection && isInitialized) {
+            deploySection.style.display = 'none';
         }
 
         return isInitialized;
@@ -533,18 +580,23 @@ async function provideLiquidity(amount) {
     }
 }
 
-async function createMarket(title, startTime, homeOdds, drawOdds, awayOdds) {
+async function createMarket(title, startTime, outcomes, depth, pricing) {
     try {
         showStatus('Creating market...', 'info');
 
-        // Sanitize title for Symbol type (max 32 chars, alphanumeric + underscore)
-        const sanitizedTitle = title.split(' ').join('_').replace(/[^a-zA-Z0-9_]/g, '').slice(0, 32);
-
         const adminAddress = StellarSdk.Address.fromString(keypair.publicKey());
         const startTimestamp = Math.floor(new Date(startTime).getTime() / 1000);
-        const homeOddsMicros = parseAmount(homeOdds);
-        const drawOddsMicros = parseAmount(drawOdds);
-        const awayOddsMicros = parseAmount(awayOdds);
+        const metadata = structVal({
+            title: StellarSdk.nativeToScVal(title, { type: 'string' }),
+            home_team: StellarSdk.nativeToScVal('', { type: 'string' }),
+            away_team: StellarSdk.nativeToScVal('', { type: 'string' }),
+            competition: StellarSdk.nativeToScVal('', { type: 'string' }),
+            venue: StellarSdk.nativeToScVal('', { type: 'string' }),
+            fixture_id: StellarSdk.nativeToScVal('', { type: 'string' }),
+            outcome_labels: StellarSdk.nativeToScVal(outcomes.map(outcome => outcome.label), { type: 'string' }),
+            image_uri: StellarSdk.nativeToScVal('', { type: 'string' })
+        });
+        const odds = StellarSdk.xdr.ScVal.scvVec(outcomes.map(outcome => i128Val(parseAmount(outcome.odds))));
 
         // Build transaction manually with correct types
         const account = await rpc.getAccount(keypair.publicKey());
@@ -556,11 +608,11 @@ async function createMarket(title, startTime, homeOdds, drawOdds, awayOdds) {
         .addOperation(contract.call(
             'create_market',
             StellarSdk.nativeToScVal(adminAddress.toString(), { type: 'address' }),
-            StellarSdk.nativeToScVal(sanitizedTitle, { type: 'symbol' }),
+            metadata,
             StellarSdk.nativeToScVal(startTimestamp, { type: 'i64' }),
-            StellarSdk.nativeToScVal(homeOddsMicros, { type: 'i128' }),
-            StellarSdk.nativeToScVal(drawOddsMicros, { type: 'i128' }),
-            StellarSdk.nativeToScVal(awayOddsMicros, { type: 'i128' })
+            odds,
+            i128Val(parseAmount(depth)),
+            enumVal(pricing)
         ))
         .setTimeout(30)
         .build();
@@ -604,6 +656,14 @@ async function placeStake(marketId, outcome, amount) {
         const userAddress = StellarSdk.Address.fromString(keypair.publicKey());
         const amountMicros = parseAmount(amount);
 
+        // Refuse the fill if the price moves more than the slippage tolerance before it lands
+        const quote = await readContract('quote_stake', BigInt(marketId), u32Val(outcome), i128Val(amountMicros));
+        if (!quote) {
+            throw new Error('Market is not taking stakes');
+        }
+        const [quotedShares] = StellarSdk.scValToNative(quote);
+        const minSharesOut = BigInt(quotedShares) * BigInt(10000 - CONFIG.slippageBps) / 10000n;
+
         const account = await rpc.getAccount(keypair.publicKey());
         let tx = new StellarSdk.TransactionBuilder(account, {
             fee: StellarSdk.BASE_FEE,
@@ -614,7 +674,9 @@ async function placeStake(marketId, outcome, amount) {
             StellarSdk.nativeToScVal(userAddress.toString(), { type: 'address' }),
             StellarSdk.nativeToScVal(marketId, { type: 'u64' }),
             StellarSdk.nativeToScVal(outcome, { type: 'u32' }),
-            StellarSdk.nativeToScVal(amountMicros, { type: 'i128' })
+            StellarSdk.nativeToScVal(amountMicros, { type: 'i128' }),
+            i128Val(minSharesOut),
+            noneVal()
         ))
         .setTimeout(30)
         .build();
@@ -648,7 +710,8 @@ async function placeStake(marketId, outcome, amount) {
     }
 }
 
-async function settleMarket(marketId, outcome) {
+// Pull-mode markets can only be resolved; their winners claim their own payouts
+async function settleMarket(marketId, outcome, method = 'settle_market') {
     try {
         showStatus('Settling market...', 'info');
         const adminAddress = StellarSdk.Address.fromString(keypair.publicKey());
@@ -659,7 +722,7 @@ async function settleMarket(marketId, outcome) {
             networkPassphrase: CONFIG.networkPassphrase
         })
         .addOperation(contract.call(
-            'settle_market',
+            method,
             StellarSdk.nativeToScVal(adminAddress.toString(), { type: 'address' }),
             StellarSdk.nativeToScVal(marketId, { type: 'u64' }),
             StellarSdk.nativeToScVal(outcome, { type: 'u32' })
@@ -738,6 +801,41 @@ async function archiveMarket(marketId) {
     }
 }
 
+// Settles or refunds the next batch of a Settling or Voiding market. Anyone can call this.
+async function continueSettlement(marketId) {
+    try {
+        showStatus('Settling next batch...', 'info');
+        const remaining = await callContract('settle_batch', BigInt(marketId), u32Val(CONFIG.settleBatchSize));
+        const left = remaining ? StellarSdk.scValToNative(remaining) : 0;
+        showStatus(left > 0 ? `Batch settled, ${left} stakes left` : 'Settlement complete!', 'success');
+        await loadAdminMarkets();
+    } catch (error) {
+        console.error('Error settling batch:', error);
+        showStatus('Failed to settle batch', 'error');
+    }
+}
+
+async function claimStake(stakeId) {
+    if (!keypair) {
+        showStatus('Please connect your wallet first', 'warning');
+        return;
+    }
+
+    try {
+        showStatus('Claiming winnings...', 'info');
+        const userAddress = StellarSdk.Address.fromString(keypair.publicKey());
+        await callContract('claim', userAddress, BigInt(stakeId));
+        showStatus('Winnings claimed!', 'success');
+        await Promise.all([
+            loadUserBalance(),
+            loadUserStakes()
+        ]);
+    } catch (error) {
+        console.error('Error claiming stake:', error);
+        showStatus('Failed to claim winnings', 'error');
+    }
+}
+
 // Data loading functions
 async function loadUserBalance() {
     if (!keypair) return;
@@ -768,31 +866,18 @@ async function loadMarkets() {
         // Try to load markets sequentially until we hit one that doesn't exist
         // Markets are created with incrementing IDs starting from 1
         for (let i = 1; i <= 100; i++) {
-            const market = await readContract('get_market', BigInt(i));
+            const market = await readMarket(i);
 
-            // If readContract returns null, the market doesn't exist - stop searching
+            // If readMarket returns null, the market doesn't exist - stop searching
             if (!market) {
                 break;
             }
 
-            const marketData = StellarSdk.scValToNative(market);
-            // MarketStatus enum is returned as array ['Active'], ['Settled'], or ['Archived']
-            const status = Array.isArray(marketData.status) ? marketData.status[0] : marketData.status;
-            if (status === 'Active') {
-                // Get current odds from CPMM
+            if (market.status === 'Active') {
+                // Get current odds from the market's reserves, falling back to its set odds
                 const currentOdds = await readContract('get_current_odds', BigInt(i));
-                if (currentOdds) {
-                    const oddsNative = StellarSdk.scValToNative(currentOdds);
-                    marketData.current_odds_home = oddsNative[0];
-                    marketData.current_odds_draw = oddsNative[1];
-                    marketData.current_odds_away = oddsNative[2];
-                } else {
-                    // Fallback to static odds
-                    marketData.current_odds_home = marketData.odds_home;
-                    marketData.current_odds_draw = marketData.odds_draw;
-                    marketData.current_odds_away = marketData.odds_away;
-                }
-                markets.push({ id: i, ...marketData, status });
+                market.currentOdds = currentOdds ? StellarSdk.scValToNative(currentOdds) : market.odds;
+                markets.push(market);
             }
         }
 
@@ -806,23 +891,17 @@ async function loadMarkets() {
             container.innerHTML = markets.map(market => `
                 <div class="market-card" data-market-id="${market.id}">
                     <div class="market-header">
-                        <h3 class="market-title">${market.title}</h3>
+                        <h3 class="market-title">${escapeHtml(market.title)}</h3>
                         <div class="market-time">${formatDateTime(market.start_time)}</div>
                         <span class="market-status ${market.status.toLowerCase()}">${market.status}</span>
                     </div>
                     <div class="outcomes">
-                        <div class="outcome" data-outcome="0">
-                            <span class="outcome-name">Home Win</span>
-                            <span class="outcome-odds" data-market-id="${market.id}" data-outcome="0">$${formatAmount(market.current_odds_home)}</span>
-                        </div>
-                        <div class="outcome" data-outcome="1">
-                            <span class="outcome-name">Draw</span>
-                            <span class="outcome-odds" data-market-id="${market.id}" data-outcome="1">$${formatAmount(market.current_odds_draw)}</span>
-                        </div>
-                        <div class="outcome" data-outcome="2">
-                            <span class="outcome-name">Away Win</span>
-                            <span class="outcome-odds" data-market-id="${market.id}" data-outcome="2">$${formatAmount(market.current_odds_away)}</span>
-                        </div>
+                        ${market.outcomeLabels.map((label, index) => `
+                            <div class="outcome" data-outcome="${index}">
+                                <span class="outcome-name">${escapeHtml(label)}</span>
+                                <span class="outcome-odds" data-market-id="${market.id}" data-outcome="${index}">$${formatAmount(market.currentOdds[index])}</span>
+                            </div>
+                        `).join('')}
                     </div>
                 </div>
             `).join('');
@@ -871,17 +950,12 @@ async function updateLiveOdds() {
             if (!currentOdds) continue;
 
             const oddsNative = StellarSdk.scValToNative(currentOdds);
-            const [oddsHome, oddsDraw, oddsAway] = oddsNative;
 
             // Update each outcome's odds
             oddsElements.forEach(el => {
                 if (el.dataset.marketId !== marketId) return;
 
-                const outcome = parseInt(el.dataset.outcome);
-                let newOdds;
-                if (outcome === 0) newOdds = oddsHome;
-                else if (outcome === 1) newOdds = oddsDraw;
-                else if (outcome === 2) newOdds = oddsAway;
+                const newOdds = oddsNative[parseInt(el.dataset.outcome)];
 
                 const oldOdds = parseFloat(el.textContent.replace('$', ''));
                 const newOddsFormatted = formatAmount(newOdds);
@@ -918,91 +992,19 @@ function stopOddsUpdates() {
 
 async function updateLiveStakeValues() {
     try {
-        // Get all stake cards with value elements
-        const valueElements = document.querySelectorAll('[data-stake-id$="-value"]');
-        if (valueElements.length === 0) return;
-
-        // Extract unique stake IDs and fetch their data
-        const stakeIds = new Set();
-        valueElements.forEach(el => {
-            const match = el.dataset.stakeId.match(/^(\d+)-value$/);
-            if (match) stakeIds.add(match[1]);
-        });
+        // Every open stake card has a cash-out value to refresh
+        const cashoutElements = document.querySelectorAll('[data-stake-id$="-cashout"]');
+        if (cashoutElements.length === 0) return;
+
+        for (const el of cashoutElements) {
+            const match = el.dataset.stakeId.match(/^(\d+)-cashout$/);
+            if (!match) continue;
 
-        for (const stakeId of stakeIds) {
             try {
-                // Get stake data
-                const stakeResult = await readContract('get_stake', BigInt(stakeId));
+                const stakeResult = await readContract('get_stake', BigInt(match[1]));
                 if (!stakeResult) continue;
 
-                const stake = StellarSdk.scValToNative(stakeResult);
-
-                // Get current market data
-                const market = await readContract('get_market', BigInt(stake.market_id));
-                if (!market) continue;
-
-                const marketData = StellarSdk.scValToNative(market);
-                const status = Array.isArray(marketData.status) ? marketData.status[0] : marketData.status;
-
-                // Only update active markets
-                if (status !== 'Active') continue;
-
-                // Get current odds
-                const currentOdds = await readContract('get_current_odds', BigInt(stake.market_id));
-                if (!currentOdds) continue;
-
-                const oddsNative = StellarSdk.scValToNative(currentOdds);
-                const currentPrice = oddsNative[stake.outcome];
-
-                // Update current odds display
-                const oddsElement = document.querySelector(`[data-stake-id="${stakeId}-current-odds"]`);
-                if (oddsElement) {
-                    const newOddsText = `$${formatAmount(currentPrice)}`;
-                    if (oddsElement.textContent !== newOddsText) {
-                        oddsElement.textContent = newOddsText;
-                        oddsElement.classList.add('odds-changed');
-                        setTimeout(() => oddsElement.classList.remove('odds-changed'), 500);
-                    }
-                }
-
-                // Get total liquidity
-                const totalLiq = await readContract('total_liquidity');
-                const totalLiquidity = totalLiq ? Number(StellarSdk.scValToNative(totalLiq)) : 0;
-
-                // Get the reserve (total shares) for this outcome
-                const reserve = stake.outcome === 0 ? marketData.reserve_home :
-                               stake.outcome === 1 ? marketData.reserve_draw :
-                               marketData.reserve_away;
-
-                // Calculate current value: shares * (total_liq / reserve)
-                // Capped at shares to prevent showing profit from own liquidity addition
-                const shares = Number(stake.amount);
-                const totalShares = Number(reserve);
-                const proportionalValue = totalShares > 0 ? (shares * totalLiquidity) / totalShares : 0;
-                const currentValue = proportionalValue < shares ? proportionalValue : shares;
-
-                // Update current value display
-                const valueElement = document.querySelector(`[data-stake-id="${stakeId}-value"]`);
-                if (valueElement) {
-                    const newValueText = `$${(currentValue / CONFIG.decimals).toFixed(2)}`;
-                    if (valueElement.textContent !== newValueText) {
-                        valueElement.textContent = newValueText;
-                        valueElement.classList.add('odds-changed');
-                        setTimeout(() => valueElement.classList.remove('odds-changed'), 500);
-                    }
-
-                    // Update profit/loss indicator
-                    const entryValue = Number(stake.amount) * Number(stake.price) / CONFIG.decimals;
-                    const displayValue = currentValue / CONFIG.decimals;
-
-                    if (displayValue > entryValue) {
-                        valueElement.classList.add('profit');
-                        valueElement.classList.remove('loss');
-                    } else if (displayValue < entryValue) {
-                        valueElement.classList.add('loss');
-                        valueElement.classList.remove('profit');
-                    }
-                }
+                await updateStakeCurrentValue(StellarSdk.scValToNative(stakeResult));
             } catch (error) {
                 // Skip individual stake errors
                 continue;
@@ -1044,17 +1046,15 @@ async function loadAdminMarkets() {
         // Try to load markets sequentially until we hit one that doesn't exist
         // Load all markets (including settled ones) for admin view
         for (let i = 1; i <= 100; i++) {
-            const market = await readContract('get_market', BigInt(i));
+            const market = await readMarket(i);
 
-            // If readContract returns null, the market doesn't exist - stop searching
+            // If readMarket returns null, the market doesn't exist - stop searching
             if (!market) {
                 break;
             }
 
-            const marketData = StellarSdk.scValToNative(market);
-            // MarketStatus enum is returned as array ['Active'], ['Settled'], or ['Archived']
-            const status = Array.isArray(marketData.status) ? marketData.status[0] : marketData.status;
-            markets.push({ id: i, ...marketData, status });
+            markets.push(market);
+            adminMarkets[i] = market;
         }
 
         if (markets.length === 0) {
@@ -1067,16 +1067,21 @@ async function loadAdminMarkets() {
             container.innerHTML = markets.map(market => `
                 <div class="admin-market-item">
                     <div class="admin-market-info">
-                        <h4>Market ${market.id}: ${market.title}</h4>
+                        <h4>Market ${market.id}: ${escapeHtml(market.title)}</h4>
                         <div class="admin-market-meta">
-                            Status: ${market.status} | Start: ${formatDateTime(market.start_time)} | Stakers: ${market.staker_count}
+                            Status: ${market.status} | Start: ${formatDateTime(market.start_time)} | Stakers: ${market.staker_count} | Outcomes: ${market.outcomeLabels.length} | Settlement: ${market.settlement}
                         </div>
                     </div>
                     <div class="admin-market-actions">
-                        ${market.status === 'Active' ? `
-                            <button class="btn btn-warning btn-sm" onclick="settleMarketPrompt(${market.id})">Settle</button>
+                        ${market.status === 'Active' || market.status === 'Suspended' ? `
+                            <button class="btn btn-warning btn-sm" onclick="settleMarketPrompt(${market.id})">${market.settlement === 'Pull' ? 'Resolve' : 'Settle'}</button>
+                        ` : ''}
+                        ${market.status === 'Settling' || market.status === 'Voiding' ? `
+                            <button class="btn btn-warning btn-sm" onclick="continueSettlement(${market.id})">Next Batch</button>
+                        ` : ''}
+                        ${['Active', 'Suspended', 'Settled'].includes(market.status) ? `
+                            <button class="btn btn-danger btn-sm" onclick="archiveMarketPrompt(${market.id})">Archive</button>
                         ` : ''}
-                        <button class="btn btn-danger btn-sm" onclick="archiveMarketPrompt(${market.id})">Archive</button>
                     </div>
                 </div>
             `).join('');
@@ -1100,12 +1105,10 @@ async function loadUserStakes() {
         // Iterate through markets to find user's stakes
         for (let marketId = 1; marketId <= 100; marketId++) {
             try {
-                const market = await readContract('get_market', BigInt(marketId));
+                const market = await readMarket(marketId);
                 if (!market) break;
 
-                const marketData = StellarSdk.scValToNative(market);
-                const status = Array.isArray(marketData.status) ? marketData.status[0] : marketData.status;
-                marketCache[marketId] = { ...marketData, status, id: marketId };
+                marketCache[marketId] = market;
 
                 // Get all stakes for this market
                 const stakesResult = await readContract('get_market_stakes', BigInt(marketId));
@@ -1132,17 +1135,16 @@ async function loadUserStakes() {
         if (userStakes.length === 0) {
             container.innerHTML = '<div class="no-markets"><div class="no-markets-content"><div class="no-markets-icon">🎫</div><h3>No Stakes Yet</h3><p>Your staking history will appear here after placing stakes</p></div></div>';
         } else {
-            const outcomeNames = ['Home Win', 'Draw', 'Away Win'];
             container.innerHTML = userStakes.map(stake => `
                 <div class="stake-card" data-stake-id="${stake.id}">
                     <div class="stake-header">
-                        <h4 class="stake-title">${stake.market.title}</h4>
+                        <h4 class="stake-title">${escapeHtml(stake.market.title)}</h4>
                         <span class="market-status ${stake.market.status.toLowerCase()}">${stake.market.status}</span>
                     </div>
                     <div class="stake-details-grid">
                         <div class="stake-detail">
                             <span class="stake-label">Outcome</span>
-                            <span class="stake-value">${outcomeNames[stake.outcome]}</span>
+                            <span class="stake-value">${escapeHtml(stake.market.outcomeLabels[stake.outcome])}</span>
                         </div>
                         <div class="stake-detail">
                             <span class="stake-label">Entry Odds</span>
@@ -1163,7 +1165,12 @@ async function loadUserStakes() {
                     </div>
                     ${stake.market.status === 'Active' ? `
                         <button class="btn btn-warning cash-out-btn" onclick="cashOutStake(${stake.id})" style="margin-top: 10px; width: 100%;">
-                            Cash Out (5% fee)
+                            Cash Out
+                        </button>
+                    ` : ''}
+                    ${isClaimable(stake) ? `
+                        <button class="btn btn-success" onclick="claimStake(${stake.id})" style="margin-top: 10px; width: 100%;">
+                            Claim $${formatAmount(stake.amount)}
                         </button>
                     ` : ''}
                 </div>
@@ -1184,14 +1191,17 @@ async function loadUserStakes() {
     }
 }
 
+// A winning stake in a market resolved for pull settlement, waiting for its owner to claim it
+function isClaimable(stake) {
+    const market = stake.market;
+    return market.resolved_by_claim
+        && (market.status === 'Settled' || market.status === 'Archived')
+        && market.winning_outcome !== undefined && market.winning_outcome !== null
+        && Number(market.winning_outcome) === Number(stake.outcome);
+}
+
 async function updateStakeCurrentValue(stake) {
     try {
-        // Get current market data
-        const market = await readContract('get_market', BigInt(stake.market_id));
-        if (!market) return;
-
-        const marketData = StellarSdk.scValToNative(market);
-
         // Get current odds for the market
         const currentOdds = await readContract('get_current_odds', BigInt(stake.market_id));
         if (!currentOdds) return;
@@ -1202,39 +1212,22 @@ async function updateStakeCurrentValue(stake) {
         // Update current odds display
         const oddsElement = document.querySelector(`[data-stake-id="${stake.id}-current-odds"]`);
         if (oddsElement) {
-            oddsElement.textContent = `$${formatAmount(currentPrice)}`;
+            const newOddsText = `$${formatAmount(currentPrice)}`;
+            if (oddsElement.textContent !== newOddsText) {
+                oddsElement.textContent = newOddsText;
+                oddsElement.classList.add('odds-changed');
+                setTimeout(() => oddsElement.classList.remove('odds-changed'), 500);
+            }
         }
 
-        // Get the reserve (USD amount staked on this outcome)
-        const reserve = stake.outcome === 0 ? marketData.reserve_home :
-                       stake.outcome === 1 ? marketData.reserve_draw :
-                       marketData.reserve_away;
-
-        // Calculate current value with exit slippage (mirrors contract logic)
-        const shares = Number(stake.amount);
-        const reserveNum = Number(reserve);
-        const totalReserve = Number(marketData.reserve_home) + Number(marketData.reserve_draw) + Number(marketData.reserve_away);
-
-        // Price before exit
-        const priceBeforeExit = totalReserve > 0 ? (reserveNum * CONFIG.decimals) / totalReserve : 0;
-
-        // Estimated payout
-        const estimatedPayout = (shares * priceBeforeExit) / CONFIG.decimals;
-
-        // Price after removing estimated payout
-        const priceAfterExit = reserveNum <= estimatedPayout ? 0 :
-            ((reserveNum - estimatedPayout) * CONFIG.decimals) / (totalReserve - estimatedPayout);
-
-        // Average exit price (with slippage)
-        const avgExitPrice = (priceBeforeExit + priceAfterExit) / 2;
+        // The contract prices the cash-out exactly as cash_out would, fee included
+        const quote = await readContract('quote_cash_out', BigInt(stake.id));
+        if (!quote) return;
+        const [, , netPayout] = StellarSdk.scValToNative(quote);
 
         // Settlement value: fixed $1 per share (what you get if you win)
-        const settlementValue = shares;
-
-        // Cashout value: current market value (before 5% fee)
-        const cashoutValueBeforeFee = (shares * avgExitPrice) / CONFIG.decimals;
-        const cashoutFee = cashoutValueBeforeFee * 0.05;
-        const cashoutValueAfterFee = cashoutValueBeforeFee - cashoutFee;
+        const settlementValue = Number(stake.amount);
+        const cashoutValueAfterFee = Number(netPayout);
 
         // Update Settlement Value
         const settlementElement = document.querySelector(`[data-stake-id="${stake.id}-settlement"]`);
@@ -1247,14 +1240,13 @@ async function updateStakeCurrentValue(stake) {
         if (cashoutElement) {
             cashoutElement.textContent = `$${(cashoutValueAfterFee / CONFIG.decimals).toFixed(2)}`;
 
-            // Calculate entry value for comparison (amount originally paid)
-            const entryValue = (shares * Number(stake.price)) / CONFIG.decimals;
-            const displayValue = cashoutValueAfterFee / CONFIG.decimals;
+            // Compare against the amount originally paid
+            const entryValue = Number(stake.cost);
 
-            if (displayValue > entryValue) {
+            if (cashoutValueAfterFee > entryValue) {
                 cashoutElement.classList.add('profit');
                 cashoutElement.classList.remove('loss');
-            } else if (displayValue < entryValue) {
+            } else if (cashoutValueAfterFee < entryValue) {
                 cashoutElement.classList.add('loss');
                 cashoutElement.classList.remove('profit');
             }
@@ -1274,7 +1266,13 @@ async function cashOutStake(stakeId) {
         showStatus('Processing cash out...', 'info');
 
         const userAddress = StellarSdk.Address.fromString(keypair.publicKey());
-        await callContract('cash_out', userAddress, BigInt(stakeId));
+        const quote = await readContract('quote_cash_out', BigInt(stakeId));
+        if (!quote) {
+            throw new Error('Stake cannot be cashed out');
+        }
+        const [, , netPayout] = StellarSdk.scValToNative(quote);
+        const minPayout = BigInt(netPayout) * BigInt(10000 - CONFIG.slippageBps) / 10000n;
+        await callContract('cash_out', userAddress, BigInt(stakeId), i128Val(minPayout), noneVal());
 
         showStatus('Successfully cashed out!', 'success');
 
@@ -1300,43 +1298,19 @@ async function openStakingModal(market, outcomeId) {
     const selectedOutcome = document.getElementById('selected-outcome');
     const selectedOdds = document.getElementById('selected-odds');
 
-    const outcomes = ['Home Win', 'Draw', 'Away Win'];
-
-    // Use current odds if available, fallback to starting odds
-    const currentOdds = [
-        market.current_odds_home || market.odds_home,
-        market.current_odds_draw || market.odds_draw,
-        market.current_odds_away || market.odds_away
-    ];
-
-    // Get current CPMM odds for the market
-    let currentMarketOdds = null;
-    try {
-        const currentOddsResult = await readContract('get_current_odds', BigInt(market.id));
-        if (currentOddsResult) {
-            const oddsNative = StellarSdk.scValToNative(currentOddsResult);
-            currentMarketOdds = oddsNative;
-        }
-    } catch (error) {
-        console.error('Error fetching current odds:', error);
-    }
+    // Use current odds if available, fallback to the market's set odds
+    const currentOdds = market.currentOdds || market.odds;
 
     currentStake = {
         marketId: market.id,
         outcome: parseInt(outcomeId),
-        odds: currentOdds[outcomeId],
-        currentOdds: currentMarketOdds,
-        reserves: {
-            home: market.reserve_home || 0,
-            draw: market.reserve_draw || 0,
-            away: market.reserve_away || 0
-        }
+        odds: currentOdds[outcomeId]
     };
 
     modalTitle.textContent = 'Place Stake';
     modalMatchTitle.textContent = market.title;
     modalMatchTime.textContent = formatDateTime(market.start_time);
-    selectedOutcome.textContent = outcomes[outcomeId];
+    selectedOutcome.textContent = market.outcomeLabels[outcomeId];
     selectedOdds.textContent = `$${formatAmount(currentOdds[outcomeId])}`;
 
     modal.classList.add('show');
@@ -1361,70 +1335,37 @@ async function updatePayout() {
     const potentialProfit = document.getElementById('potential-profit');
 
     if (currentStake && stakeAmount > 0) {
-        // Get current market data to calculate CPMM price
-        let currentPrice = 0;
-        let quotePrice = 0;
+        const oddsNum = typeof currentStake.odds === 'bigint' ? Number(currentStake.odds) : currentStake.odds;
+        let currentPrice = oddsNum;
+        let quotePrice = oddsNum;
+        let shares = stakeAmount * CONFIG.decimals * CONFIG.decimals / oddsNum;
         let slippagePercent = 0;
 
         try {
-            // Fetch fresh market data
-            const market = await readContract('get_market', BigInt(currentStake.marketId));
-            if (market) {
-                const marketData = StellarSdk.scValToNative(market);
-
-                // Get reserves
-                const reserveHome = Number(marketData.reserve_home) || 0;
-                const reserveDraw = Number(marketData.reserve_draw) || 0;
-                const reserveAway = Number(marketData.reserve_away) || 0;
-                const totalReserve = reserveHome + reserveDraw + reserveAway;
-
-                // Get current reserve for selected outcome
-                let currentReserve = 0;
-                if (currentStake.outcome === 0) currentReserve = reserveHome;
-                else if (currentStake.outcome === 1) currentReserve = reserveDraw;
-                else if (currentStake.outcome === 2) currentReserve = reserveAway;
-
-                // Calculate current price (before stake)
-                if (totalReserve > 0 && currentReserve > 0) {
-                    currentPrice = (currentReserve * CONFIG.decimals) / totalReserve;
-                } else {
-                    // Fallback to initial odds
-                    const oddsNum = typeof currentStake.odds === 'bigint' ? Number(currentStake.odds) : currentStake.odds;
-                    currentPrice = oddsNum;
-                }
-
-                // Calculate quote price (middle price with slippage)
-                const stakeAmountMicros = stakeAmount * CONFIG.decimals;
-                const newReserve = currentReserve + stakeAmountMicros;
-                const newTotal = totalReserve + stakeAmountMicros;
-
-                // Price after stake
-                const priceAfter = newTotal > 0 ? (newReserve * CONFIG.decimals) / newTotal : currentPrice;
+            // Fresh price for the outcome, then the exact fill from the contract's own pricing
+            const currentOdds = await readContract('get_current_odds', BigInt(currentStake.marketId));
+            if (currentOdds) {
+                currentPrice = Number(StellarSdk.scValToNative(currentOdds)[currentStake.outcome]);
+            }
 
-                // Quote price is the average of before and after (what user actually gets)
-                quotePrice = (currentPrice + priceAfter) / 2;
+            const quote = await readContract('quote_stake', BigInt(currentStake.marketId), u32Val(currentStake.outcome), i128Val(parseAmount(stakeAmount)));
+            if (quote) {
+                const [quotedShares, avgPrice] = StellarSdk.scValToNative(quote);
+                shares = Number(quotedShares);
+                quotePrice = Number(avgPrice);
+            }
 
-                // Calculate slippage percentage (how much worse the quote price is vs current)
-                // When buying, quote price > current price means you pay more (negative slippage)
-                if (quotePrice > 0 && currentPrice > 0) {
-                    slippagePercent = ((quotePrice - currentPrice) / currentPrice) * 100;
-                }
-            } else {
-                // Fallback to static odds if market data unavailable
-                const oddsNum = typeof currentStake.odds === 'bigint' ? Number(currentStake.odds) : currentStake.odds;
-                currentPrice = oddsNum;
-                quotePrice = oddsNum;
+            // Calculate slippage percentage (how much worse the quote price is vs current)
+            // When buying, quote price > current price means you pay more (negative slippage)
+            if (quotePrice > 0 && currentPrice > 0) {
+                slippagePercent = ((quotePrice - currentPrice) / currentPrice) * 100;
             }
         } catch (error) {
             console.error('Error calculating quote price:', error);
-            // Fallback to static odds
-            const oddsNum = typeof currentStake.odds === 'bigint' ? Number(currentStake.odds) : currentStake.odds;
-            currentPrice = oddsNum;
-            quotePrice = oddsNum;
         }
 
-        // Calculate payout and profit using quote price
-        const payout = stakeAmount * CONFIG.decimals / quotePrice;
+        // Each share pays $1 if the outcome wins
+        const payout = shares / CONFIG.decimals;
         const profit = payout - stakeAmount;
 
         // Update UI
@@ -1444,10 +1385,15 @@ async function updatePayout() {
 }
 
 // Admin helper functions
+let adminMarkets = {};
+
 function settleMarketPrompt(marketId) {
-    const outcome = prompt('Enter winning outcome (0=Home Win, 1=Draw, 2=Away Win):');
-    if (outcome !== null && ['0', '1', '2'].includes(outcome)) {
-        settleMarket(marketId, parseInt(outcome));
+    const market = adminMarkets[marketId];
+    const labels = market ? market.outcomeLabels : [];
+    const options = labels.map((label, index) => `${index}=${label}`).join(', ');
+    const outcome = prompt(`Enter winning outcome (${options}):`);
+    if (outcome !== null && /^\d+$/.test(outcome) && parseInt(outcome) < labels.length) {
+        settleMarket(marketId, parseInt(outcome), market.settlement === 'Pull' ? 'resolve_market' : 'settle_market');
     }
 }
 
@@ -1457,28 +1403,77 @@ function archiveMarketPrompt(marketId) {
     }
 }
 
-function validateOdds() {
-    const homeOdds = parseFloat(document.getElementById('home-odds').value) || 0;
-    const drawOdds = parseFloat(document.getElementById('draw-odds').value) || 0;
-    const awayOdds = parseFloat(document.getElementById('away-odds').value) || 0;
+// Outcome rows of the create-market form, one label and starting price each
+const DEFAULT_OUTCOMES = [
+    { label: 'Home Win', odds: '' },
+    { label: 'Draw', odds: '' },
+    { label: 'Away Win', odds: '' }
+];
+const MIN_OUTCOMES = 2;
+const MAX_OUTCOMES = 32;
+
+function addOutcomeRow(label = '', odds = '') {
+    const container = document.getElementById('outcome-rows');
+    if (!container || container.children.length >= MAX_OUTCOMES) return;
+
+    const row = document.createElement('div');
+    row.className = 'odds-input outcome-row';
+    row.innerHTML = `
+        <input type="text" class="outcome-label-input" placeholder="Outcome label" maxlength="256">
+        <input type="number" class="outcome-odds-input" placeholder="0.33" step="0.01" min="0.01" max="0.97">
+        <button type="button" class="btn btn-secondary btn-sm remove-outcome">&times;</button>
+    `;
+    row.querySelector('.outcome-label-input').value = label;
+    row.querySelector('.outcome-odds-input').value = odds;
+    row.querySelector('.remove-outcome').addEventListener('click', () => {
+        if (container.children.length > MIN_OUTCOMES) {
+            row.remove();
+            validateOdds();
+        }
+    });
+    container.appendChild(row);
+}
+
+function resetOutcomeRows() {
+    const container = document.getElementById('outcome-rows');
+    if (!container) return;
+
+    container.innerHTML = '';
+    DEFAULT_OUTCOMES.forEach(outcome => addOutcomeRow(outcome.label, outcome.odds));
+    validateOdds();
+}
+
+function getOutcomeRows() {
+    return Array.from(document.querySelectorAll('#outcome-rows .outcome-row')).map(row => ({
+        label: row.querySelector('.outcome-label-input').value.trim(),
+        odds: row.querySelector('.outcome-odds-input').value
+    }));
+}
 
-    const sum = homeOdds + drawOdds + awayOdds;
+function validateOdds() {
+    const outcomes = getOutcomeRows();
+    const sum = outcomes.reduce((total, outcome) => total + (parseFloat(outcome.odds) || 0), 0);
+    const labelled = outcomes.length >= MIN_OUTCOMES && outcomes.every(outcome => outcome.label !== '');
     const oddsSum = document.getElementById('odds-sum');
     const validation = document.getElementById('odds-validation');
+    const valid = labelled && Math.abs(sum - 0.99) < 0.01;
 
     if (oddsSum) oddsSum.textContent = sum.toFixed(2);
 
     if (validation) {
-        if (Math.abs(sum - 0.99) < 0.01) {
+        if (valid) {
             validation.textContent = '✓ Valid';
             validation.className = 'validation-message valid';
+        } else if (!labelled) {
+            validation.textContent = '✗ Every outcome needs a label';
+            validation.className = 'validation-message invalid';
         } else {
             validation.textContent = '✗ Must sum to $0.99';
             validation.className = 'validation-message invalid';
         }
     }
 
-    return Math.abs(sum - 0.99) < 0.01;
+    return valid;
 }
 
 // Admin initialization
@@ -1494,11 +1489,17 @@ async function initializeAdmin() {
         }
     }
 
-    // Add odds validation listeners
-    const oddsInputs = document.querySelectorAll('#home-odds, #draw-odds, #away-odds');
-    oddsInputs.forEach(input => {
-        input.addEventListener('input', validateOdds);
-    });
+    // Outcome rows, revalidated as they are edited
+    resetOutcomeRows();
+    const outcomeRows = document.getElementById('outcome-rows');
+    if (outcomeRows) outcomeRows.addEventListener('input', validateOdds);
+    const addOutcomeBtn = document.getElementById('add-outcome');
+    if (addOutcomeBtn) {
+        addOutcomeBtn.addEventListener('click', () => {
+            addOutcomeRow();
+            validateOdds();
+        });
+    }
 
     // Load admin data
     checkContractStatus();
@@ -1586,7 +1587,6 @@ document.addEventListener('DOMContentLoaded', function() {
     }
 
     // Admin handlers
-    const initializeBtn = document.getElementById('initialize-contract');
     const provideLiquidityBtn = document.getElementById('provide-liquidity');
     const createMarketBtn = document.getElementById('create-market');
     const settleMarketBtn = document.getElementById('settle-market');
@@ -1595,10 +1595,6 @@ document.addEventListener('DOMContentLoaded', function() {
 
     if (contractIdDisplay) contractIdDisplay.innerText = CONFIG.contractId;
 
-    if (initializeBtn) {
-        initializeBtn.addEventListener('click', initializeContract);
-    }
-
     if (provideLiquidityBtn) {
         provideLiquidityBtn.addEventListener('click', () => {
             const amount = document.getElementById('liquidity-amount').value;
@@ -1611,24 +1607,20 @@ document.addEventListener('DOMContentLoaded', function() {
 
     if (createMarketBtn) {
         createMarketBtn.addEventListener('click', () => {
-            const title = document.getElementById('market-title').value;
+            const title = document.getElementById('market-title').value.trim();
             const startTime = document.getElementById('market-start-time').value;
-            const homeOdds = document.getElementById('home-odds').value;
-            const drawOdds = document.getElementById('draw-odds').value;
-            const awayOdds = document.getElementById('away-odds').value;
+            const depth = document.getElementById('market-depth').value;
+            const pricing = document.getElementById('market-pricing').value;
 
-            if (title && startTime && validateOdds()) {
-                createMarket(title, startTime, homeOdds, drawOdds, awayOdds);
+            if (title && startTime && parseFloat(depth) >= 1 && validateOdds()) {
+                createMarket(title, startTime, getOutcomeRows(), depth, pricing);
 
                 // Reset form
                 document.getElementById('market-title').value = '';
                 document.getElementById('market-start-time').value = '';
-                document.getElementById('home-odds').value = '';
-                document.getElementById('draw-odds').value = '';
-                document.getElementById('away-odds').value = '';
-                validateOdds();
+                resetOutcomeRows();
             } else {
-                showStatus('Please fill all fields and ensure odds sum to $0.99', 'error');
+                showStatus('Please fill all fields, use a depth of at least $1 and ensure odds sum to $0.99', 'error');
             }
         });
     }